@@ -0,0 +1,189 @@
+//! Command-line subcommands that run without opening a window.
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use crate::{
+    HISTOGRAM_POINTS,
+    ifs::{Leaf, System},
+    NAME,
+    projection::View,
+    render::{
+        histogram::{Histogram, ToneMapping},
+        implicit::Implicit,
+        raster,
+        trace::{self, TraceSettings},
+    },
+    RENDER_MAX_LEAVES,
+    scene::Scene,
+    VERSION,
+};
+use failure::Fallible;
+use na::Point3;
+use std::str::FromStr;
+
+pub fn app() -> App<'static, 'static> {
+    App::new(NAME)
+        .version(VERSION)
+        .setting(AppSettings::VersionlessSubcommands)
+        .subcommand(SubCommand::with_name("render")
+            .about("Render a scene file to a PNG without opening a window")
+            .arg(Arg::with_name("scene")
+                .help("Scene file, as written by Ctrl-S")
+                .required(true)
+                .index(1))
+            .arg(Arg::with_name("out")
+                .long("out")
+                .short("o")
+                .takes_value(true)
+                .default_value("render.png")
+                .help("Output PNG path"))
+            .arg(Arg::with_name("size")
+                .long("size")
+                .takes_value(true)
+                .default_value("1024x1024")
+                .help("Image size as WIDTHxHEIGHT"))
+            .arg(Arg::with_name("camera")
+                .long("camera")
+                .takes_value(true)
+                .help("Eye position as x,y,z, optionally followed by a target as x,y,z [default: 0,0,-4,0,0,0]"))
+            .arg(Arg::with_name("fov")
+                .long("fov")
+                .takes_value(true)
+                .default_value("45")
+                .help("Vertical field of view in degrees"))
+            .arg(Arg::with_name("mode")
+                .long("mode")
+                .takes_value(true)
+                .possible_values(&["cubes", "density"])
+                .default_value("cubes")
+                .help("Draw the leaf cubes at --depth, or a density histogram of chaos game points"))
+            .arg(Arg::with_name("renderer")
+                .long("renderer")
+                .takes_value(true)
+                .possible_values(&["raster", "trace", "implicit"])
+                .default_value("raster")
+                .help("Draw cubes with the rasterizer, ray trace them with shadows and ambient occlusion, or ray trace the \
+                       tree directly down to pixel size, with --depth as the limit"))
+            .arg(Arg::with_name("samples")
+                .long("samples")
+                .takes_value(true)
+                .help("Camera rays per pixel, for trace and implicit [default: 4]"))
+            .arg(Arg::with_name("ao-samples")
+                .long("ao-samples")
+                .takes_value(true)
+                .help("Ambient occlusion rays per hit, for trace and implicit; 0 disables it [default: 16]"))
+            .arg(Arg::with_name("depth")
+                .long("depth")
+                .takes_value(true)
+                .default_value("4")
+                .help("Iteration depth, for cubes; raster and trace are limited to depths with at most 4000000 leaves"))
+            .arg(Arg::with_name("points")
+                .long("points")
+                .takes_value(true)
+                .help("Number of chaos game points, for density [default: 50000000]")))
+}
+
+/// Run the subcommand in `matches`, if any. Returns `false` if there wasn't one, in which case
+/// the viewer should start.
+pub fn run(matches: &ArgMatches) -> Fallible<bool> {
+    match matches.subcommand() {
+        ("render", Some(matches)) => render(matches).map(|_| true),
+        _ => Ok(false),
+    }
+}
+
+fn render(matches: &ArgMatches) -> Fallible<()> {
+    let scene = Scene::load(matches.value_of("scene").unwrap())?;
+    let out = matches.value_of("out").unwrap();
+
+    let (width, height) = parse_size(matches.value_of("size").unwrap())?;
+
+    let mut view = match matches.value_of("camera") {
+        Some(camera) => parse_camera(camera)?,
+        None => View::default(),
+    };
+    view.fovy = parse::<f32>("fov", matches.value_of("fov").unwrap())?.to_radians();
+
+    let system = System::new(scene.components.iter().map(|comp| comp.map()).collect());
+
+    let image = match matches.value_of("mode").unwrap() {
+        "density" => {
+            let points = match matches.value_of("points") {
+                Some(points) => parse("points", points)?,
+                None => HISTOGRAM_POINTS,
+            };
+
+            Histogram::render(&system, &view.viewport(width, height), points).to_image(&ToneMapping::default())
+        },
+
+        _ => {
+            let depth = parse("depth", matches.value_of("depth").unwrap())?;
+
+            let mut settings = TraceSettings::default();
+
+            if let Some(samples) = matches.value_of("samples") {
+                settings.samples = parse("samples", samples)?;
+            }
+            if let Some(ao_samples) = matches.value_of("ao-samples") {
+                settings.ao_samples = parse("ao-samples", ao_samples)?;
+            }
+
+            match matches.value_of("renderer").unwrap() {
+                "trace" => trace::trace(&leaves(&system, depth)?, &view, width, height, &settings),
+
+                "implicit" => {
+                    let implicit = Implicit::new(&system, &view, height, depth)?;
+
+                    trace::trace_surface(&implicit, &view, width, height, &settings)
+                },
+
+                _ => raster::rasterize(&leaves(&system, depth)?, &view, width, height),
+            }
+        },
+    };
+
+    image.save_png(out)
+}
+
+/// Every leaf of `system` at `depth`, as long as there are few enough to hold in memory. Only the
+/// implicit renderer can go deeper.
+fn leaves(system: &System, depth: usize) -> Fallible<Vec<Leaf>> {
+    match system.leaf_count(depth) {
+        Some(count) if count <= RENDER_MAX_LEAVES => Ok(system.par_leaves(depth)),
+        _ => bail!("depth {} has more than {} leaves; use a lower --depth or --renderer implicit", depth, RENDER_MAX_LEAVES),
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Fallible<T> {
+    value.trim().parse().map_err(|_| format_err!("invalid value for --{}: {:?}", name, value))
+}
+
+fn parse_size(size: &str) -> Fallible<(usize, usize)> {
+    let mut parts = size.splitn(2, |c| c == 'x' || c == 'X');
+
+    let width: usize = parse("size", parts.next().unwrap_or(""))?;
+    let height: usize = parse("size", parts.next().ok_or_else(|| format_err!("size must be WIDTHxHEIGHT, got {:?}", size))?)?;
+
+    if width == 0 || height == 0 {
+        bail!("image size must be nonzero, got {:?}", size);
+    }
+
+    Ok((width, height))
+}
+
+fn parse_camera(camera: &str) -> Fallible<View> {
+    let values = camera.split(',')
+        .map(|v| parse::<f32>("camera", v))
+        .collect::<Fallible<Vec<_>>>()?;
+
+    let target = match values.len() {
+        3 => Point3::origin(),
+        6 => Point3::new(values[3], values[4], values[5]),
+        _ => bail!("camera must be x,y,z or x,y,z,tx,ty,tz, got {:?}", camera),
+    };
+
+    Ok(View {
+        eye: Point3::new(values[0], values[1], values[2]),
+        target,
+        ..View::default()
+    })
+}
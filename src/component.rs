@@ -1,4 +1,5 @@
 use std::cmp::{Eq, PartialEq};
+use std::default::Default;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use na::{
@@ -8,81 +9,184 @@ use na::{
     Transform3,
     Matrix4,
     Isometry3,
+    Rotation3,
+    U1,
+    U3,
 };
 
-use nc::shape::Cuboid3;
+use nc::query::Ray3;
 
-use kiss3d::scene::SceneNode;
+use crate::{geometry, ifs};
 
-pub struct Component { // Component isn't Clone because we need SceneNodes
+/// Plain description of one affine map in the scene. Rendering state lives in
+/// `state::render_state`, keyed by `uid`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComponentSpec {
     pub origin: Vector3<f32>,
     pub orientation: UnitQuaternion<f32>,
     pub scale: Vector3<f32>,
+
+    /// Off-diagonal entries `(xy, xz, yz)` of a unit upper-triangular shear applied between
+    /// scaling and rotation. With this, a component can represent any affine map.
+    #[serde(default = "Vector3::zeros")]
+    pub shear: Vector3<f32>,
+
     pub color: Vector3<f32>,
-    pub scene_node: SceneNode,
+
+    /// Relative probability of this map in stochastic rendering and in the measure of each leaf.
+    /// `None` leaves it proportional to the volume the map scales by.
+    #[serde(default)]
+    pub weight: Option<f32>,
+
+    /// Identity of this component within a running session. Not persisted: loaded components
+    /// are always assigned fresh uids.
+    #[serde(skip, default = "next_uid")]
     uid: usize,
-    pub hovered: bool,
 }
 
 static UID_CTR: AtomicUsize = AtomicUsize::new(0);
 
-impl Component {
-    // don't want to impl Default because we want to always have the scene_node captured
-    pub fn new(parent: &mut SceneNode) -> Self {
-        let scale = Vector3::new(0.5, 0.5, 0.5);
+fn next_uid() -> usize {
+    UID_CTR.fetch_add(1, Ordering::Relaxed)
+}
 
-        Component {
+impl Default for ComponentSpec {
+    fn default() -> Self {
+        ComponentSpec {
             origin: Vector3::identity(),
             orientation: UnitQuaternion::identity(),
-            scale,
+            scale: Vector3::new(0.5, 0.5, 0.5),
+            shear: Vector3::zeros(),
             color: Vector3::new(0.5, 1.0, 0.5),
-            scene_node: parent.add_cube(scale[0], scale[1], scale[2]),
-            uid: UID_CTR.fetch_add(1, Ordering::Relaxed),
-            hovered: false,
+            weight: None,
+            uid: next_uid(),
         }
     }
+}
 
-    pub fn apply(&mut self) {
-        use palette::{LinSrgb, Blend};
-
-        if self.hovered {
-            let selected_color = LinSrgb::new(1.0, 0.7, 0.7);
-            let cur_color = LinSrgb::new(self.color[0], self.color[1], self.color[2]);
-            let result = selected_color.multiply(cur_color);
-
-            self.scene_node.set_color(result.red, result.green, result.blue);
-        } else {
-            self.scene_node.set_color(self.color[0], self.color[1], self.color[2]);
-        }
-
-        self.scene_node.set_local_translation(Translation3::from_vector(self.origin.clone()));
-        self.scene_node.set_local_rotation(self.orientation);
-        self.scene_node.set_local_scale(self.scale[0], self.scale[1], self.scale[2]);
+impl ComponentSpec {
+    pub fn uid(&self) -> usize {
+        self.uid
     }
 
-    pub fn cuboid(&self) -> Cuboid3<f32> {
-        Cuboid3::new(self.scale / 2.0)
+    /// A copy of this component with a uid of its own, so it can live in the scene alongside the
+    /// original.
+    pub fn duplicate(&self) -> Self {
+        ComponentSpec {
+            uid: next_uid(),
+            ..self.clone()
+        }
     }
 
     pub fn isometric_part(&self) -> Isometry3<f32> {
         Isometry3::from_parts(Translation3::from_vector(self.origin), self.orientation)
     }
 
+    pub fn shear_matrix(&self) -> Matrix4<f32> {
+        let mut shear = Matrix4::identity();
+
+        shear[(0, 1)] = self.shear[0];
+        shear[(0, 2)] = self.shear[1];
+        shear[(1, 2)] = self.shear[2];
+
+        shear
+    }
+
     pub fn transform(&self) -> Transform3<f32> {
-        Transform3::from_matrix_unchecked(self.isometric_part().to_homogeneous() * Matrix4::new_nonuniform_scaling(&self.scale))
+        Transform3::from_matrix_unchecked(self.isometric_part().to_homogeneous() * self.shear_matrix() * Matrix4::new_nonuniform_scaling(&self.scale))
+    }
+
+    /// Set this component to an arbitrary affine map, decomposing it into translation, rotation,
+    /// shear and (possibly negative) scale. Returns `false`, leaving the component untouched, if
+    /// the map is singular.
+    pub fn set_affine(&mut self, affine: &Matrix4<f32>) -> bool {
+        let qr = affine.fixed_slice::<U3, U3>(0, 0).into_owned().qr();
+        let (mut q, mut r) = (qr.q(), qr.r());
+
+        // keep q a proper rotation; any reflection ends up as a negative scale instead
+        if q.determinant() < 0.0 {
+            for i in 0..3 {
+                q[(i, 2)] = -q[(i, 2)];
+                r[(2, i)] = -r[(2, i)];
+            }
+        }
+
+        if (0..3).any(|i| r[(i, i)].abs() < 1.0e-6) {
+            return false;
+        }
+
+        self.origin = affine.fixed_slice::<U3, U1>(0, 3).into_owned();
+        self.orientation = UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(q));
+        self.scale = Vector3::new(r[(0, 0)], r[(1, 1)], r[(2, 2)]);
+        self.shear = Vector3::new(r[(0, 1)] / r[(1, 1)], r[(0, 2)] / r[(2, 2)], r[(1, 2)] / r[(2, 2)]);
+
+        true
+    }
+
+    /// Time of impact of `ray` with this component's box, found by casting the ray into the
+    /// component's local frame where the box is the unit cube. This handles shear and reflections
+    /// alike.
+    pub fn toi_with_ray(&self, ray: &Ray3<f32>) -> Option<f32> {
+        let inverse = self.transform().to_homogeneous().try_inverse()?;
+
+        geometry::cast_unit_cube(&inverse, ray, true).map(|intersection| intersection.toi)
+    }
+
+    pub fn map(&self) -> ifs::Map {
+        ifs::Map {
+            transform: self.transform().to_homogeneous(),
+            color: self.color,
+            weight: self.weight,
+        }
     }
 }
 
-impl PartialEq<Component> for Component {
+impl PartialEq<ComponentSpec> for ComponentSpec {
     #[inline]
-    fn eq(&self, other: &Component) -> bool {
+    fn eq(&self, other: &ComponentSpec) -> bool {
         self.uid == other.uid
     }
 
     #[inline]
-    fn ne(&self, other: &Component) -> bool {
+    fn ne(&self, other: &ComponentSpec) -> bool {
         self.uid != other.uid
     }
 }
 
-impl Eq for Component {}
+impl Eq for ComponentSpec {}
+
+#[cfg(test)]
+mod tests {
+    use na::{Matrix4, UnitQuaternion, Vector3};
+    use super::ComponentSpec;
+
+    fn close(a: &Matrix4<f32>, b: &Matrix4<f32>) -> bool {
+        a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1.0e-4)
+    }
+
+    #[test]
+    fn set_affine_round_trips() {
+        let mut source = ComponentSpec::default();
+        source.origin = Vector3::new(0.3, -0.2, 0.7);
+        source.orientation = UnitQuaternion::from_euler_angles(0.4, -1.1, 2.0);
+        source.shear = Vector3::new(0.25, -0.5, 0.1);
+
+        // including a reflection, which has to come back as a negative scale
+        source.scale = Vector3::new(0.6, -0.3, 0.8);
+
+        let affine = source.transform().to_homogeneous();
+
+        let mut target = ComponentSpec::default();
+        assert!(target.set_affine(&affine));
+        assert!(close(&target.transform().to_homogeneous(), &affine));
+    }
+
+    #[test]
+    fn set_affine_rejects_singular_maps() {
+        let mut comp = ComponentSpec::default();
+        let before = comp.transform().to_homogeneous();
+
+        assert!(!comp.set_affine(&Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 0.0, 1.0))));
+        assert_eq!(comp.transform().to_homogeneous(), before);
+    }
+}
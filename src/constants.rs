@@ -39,7 +39,69 @@ pub const ROTATE_ADJUST_FINE: f32 = 1.0 / 12.0;
 pub const SCALE_ADJUST_BASE: f32 = 0.06;
 pub const SCALE_ADJUST_FINE: f32 = 0.25;
 
+pub const SHEAR_ADJUST_BASE: f32 = 0.05;
+pub const SHEAR_ADJUST_FINE: f32 = 0.25;
+
 pub const COLOR_ADJUST_BASE: f32 = 2.0;
 pub const COLOR_ADJUST_FINE: f32 = 0.25;
 
-pub const MAX_CUBES: usize = 2_000;
+pub const WEIGHT_ADJUST_BASE: f32 = 0.02;
+pub const WEIGHT_ADJUST_FINE: f32 = 0.25;
+
+/// Distance along each axis between a duplicated box and its original.
+pub const DUPLICATE_OFFSET: f32 = 0.1;
+
+/// Number of steps undo can go back.
+pub const UNDO_LIMIT: usize = 256;
+
+/// Repeats of the same adjustment closer together than this (in milliseconds) undo as one step.
+pub const UNDO_COALESCE_MS: u64 = 750;
+
+/// Default number of cubes fractal iteration may draw. Past this, only the largest cells are
+/// subdivided further.
+pub const CUBE_BUDGET: usize = 50_000;
+
+/// In adaptive mode, cells are subdivided until they're smaller than this many pixels on screen.
+pub const ADAPTIVE_PIXELS: f32 = 12.0;
+
+/// In adaptive mode, the camera must hold still for this many frames before the expansion is
+/// redone for the new view.
+pub const ADAPTIVE_SETTLE_FRAMES: usize = 2;
+
+/// Depth limit for adaptive mode, which otherwise ignores the iteration depth.
+pub const ADAPTIVE_MAX_DEPTH: usize = 16;
+
+/// Number of chaos game points drawn in the window.
+pub const CHAOS_POINTS: usize = 1_000_000;
+
+/// Number of chaos game points written by a point cloud export.
+pub const CHAOS_EXPORT_POINTS: usize = 20_000_000;
+
+/// Number of chaos game points the expansion worker sends back at a time.
+pub const CHAOS_BATCH_SIZE: usize = 65_536;
+
+pub const SCENE_PATH: &'static str = "scene.ron";
+pub const OBJ_EXPORT_PATH: &'static str = "export.obj";
+pub const STL_EXPORT_PATH: &'static str = "export.stl";
+pub const PLY_EXPORT_PATH: &'static str = "export.ply";
+pub const GLTF_EXPORT_PATH: &'static str = "export.gltf";
+pub const HISTOGRAM_RENDER_PATH: &'static str = "render.png";
+pub const TRACE_RENDER_PATH: &'static str = "trace.png";
+
+/// Number of voxels along the longest side of the scene when building the STL union.
+pub const STL_VOXEL_RESOLUTION: usize = 256;
+
+/// Number of chaos game points accumulated into a density histogram render.
+pub const HISTOGRAM_POINTS: usize = 50_000_000;
+
+/// Size of offline renders started from the viewer, relative to the window.
+pub const RENDER_SCALE: f32 = 2.0;
+
+/// Most leaves expanded for a cube render, beyond which they'd take gigabytes to hold in memory.
+pub const RENDER_MAX_LEAVES: usize = 4_000_000;
+
+/// How many levels deeper than the on-screen iteration depth point clouds are exported at.
+pub const PLY_EXTRA_DEPTH: usize = 3;
+
+/// Most leaf centers written to a point cloud; the extra depth is cut short to stay under this.
+pub const PLY_MAX_POINTS: usize = 20_000_000;
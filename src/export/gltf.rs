@@ -0,0 +1,425 @@
+//! glTF 2.0 output (`.gltf` JSON plus an external `.bin` buffer).
+//!
+//! The buffer holds a single unit cube, and each leaf is a node carrying its transform matrix.
+//! glTF attaches materials to meshes rather than nodes, so there is one mesh per distinct leaf
+//! color, all sharing the cube's accessors.
+//!
+//! glTF only allows node matrices that decompose into translation, rotation and scale, which
+//! sheared leaves don't. Those are baked instead: their cube is written to the buffer already
+//! transformed, with a mesh of its own and no node matrix. Reflections are fine either way.
+
+use crate::{
+    geometry::{self, UNIT_CUBE_FACES, UNIT_CUBE_NORMALS, UNIT_CUBE_VERTICES},
+    ifs::Leaf,
+};
+use na::{Matrix3, Matrix4, U3, Vector3};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+use super::{write_f32, write_u16};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_SHORT: u32 = 5123;
+
+/// Largest cosine between columns of a leaf's linear part for it to still count as unsheared.
+/// Products of rotations drift a little from orthogonal, which isn't worth baking over.
+const ORTHOGONAL_TOLERANCE: f32 = 1.0e-4;
+
+#[derive(Serialize)]
+struct Gltf {
+    asset: Asset,
+    scene: usize,
+    scenes: Vec<Scene>,
+    nodes: Vec<Node>,
+    meshes: Vec<Mesh>,
+    materials: Vec<Material>,
+    accessors: Vec<Accessor>,
+    #[serde(rename = "bufferViews")]
+    buffer_views: Vec<BufferView>,
+    buffers: Vec<Buffer>,
+}
+
+#[derive(Serialize)]
+struct Asset {
+    version: &'static str,
+    generator: String,
+}
+
+#[derive(Serialize)]
+struct Scene {
+    nodes: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Node {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mesh: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matrix: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Mesh {
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Serialize)]
+struct Primitive {
+    attributes: Attributes,
+    indices: usize,
+    material: usize,
+}
+
+#[derive(Serialize)]
+struct Attributes {
+    #[serde(rename = "POSITION")]
+    position: usize,
+    #[serde(rename = "NORMAL")]
+    normal: usize,
+}
+
+#[derive(Serialize)]
+struct Material {
+    #[serde(rename = "pbrMetallicRoughness")]
+    pbr_metallic_roughness: PbrMetallicRoughness,
+}
+
+#[derive(Serialize)]
+struct PbrMetallicRoughness {
+    #[serde(rename = "baseColorFactor")]
+    base_color_factor: [f32; 4],
+    #[serde(rename = "metallicFactor")]
+    metallic_factor: f32,
+    #[serde(rename = "roughnessFactor")]
+    roughness_factor: f32,
+}
+
+#[derive(Serialize)]
+struct Accessor {
+    #[serde(rename = "bufferView")]
+    buffer_view: usize,
+    #[serde(rename = "byteOffset")]
+    byte_offset: usize,
+    #[serde(rename = "componentType")]
+    component_type: u32,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+struct BufferView {
+    buffer: usize,
+    #[serde(rename = "byteOffset")]
+    byte_offset: usize,
+    #[serde(rename = "byteLength")]
+    byte_length: usize,
+    target: u32,
+}
+
+#[derive(Serialize)]
+struct Buffer {
+    uri: String,
+    #[serde(rename = "byteLength")]
+    byte_length: usize,
+}
+
+/// Write `leaves` as glTF JSON to `writer` and the binary cube buffer to `bin_writer`. `bin_uri`
+/// is how the JSON refers to the buffer, normally the `.bin` file's name relative to the `.gltf`.
+pub fn write_gltf<W, B, I>(writer: W, mut bin_writer: B, bin_uri: &str, leaves: I) -> io::Result<()>
+    where W: Write, B: Write, I: IntoIterator<Item = Leaf>
+{
+    // flat-shaded cube: four vertices per face so each face gets its own normal
+    let vertex_count = 4 * UNIT_CUBE_FACES.len();
+    let index_count = 6 * UNIT_CUBE_FACES.len();
+
+    for face in UNIT_CUBE_FACES.iter() {
+        for &vertex in face.iter() {
+            for &c in UNIT_CUBE_VERTICES[vertex].iter() {
+                write_f32(&mut bin_writer, c)?;
+            }
+        }
+    }
+
+    for normal in UNIT_CUBE_NORMALS.iter() {
+        for _ in 0..4 {
+            for &c in normal.iter() {
+                write_f32(&mut bin_writer, c)?;
+            }
+        }
+    }
+
+    for face_idx in 0..UNIT_CUBE_FACES.len() {
+        let base = 4 * face_idx as u16;
+
+        for &offset in [0, 1, 2, 0, 2, 3].iter() {
+            write_u16(&mut bin_writer, base + offset)?;
+        }
+    }
+
+    let vec3_length = 12 * vertex_count;
+    let index_length = 2 * index_count;
+
+    let mut nodes = vec![Node {
+        name: crate::NAME.to_owned(),
+        mesh: None,
+        matrix: None,
+        children: Vec::new(),
+    }];
+
+    let mut meshes = Vec::new();
+    let mut materials = Vec::new();
+    let mut material_by_color = HashMap::new();
+    let mut mesh_by_color = HashMap::new();
+
+    let mut accessors = vec![
+        Accessor {
+            buffer_view: 0,
+            byte_offset: 0,
+            component_type: FLOAT,
+            count: vertex_count,
+            kind: "VEC3",
+            min: Some(vec![-0.5, -0.5, -0.5]),
+            max: Some(vec![0.5, 0.5, 0.5]),
+        },
+        Accessor {
+            buffer_view: 1,
+            byte_offset: 0,
+            component_type: FLOAT,
+            count: vertex_count,
+            kind: "VEC3",
+            min: None,
+            max: None,
+        },
+        Accessor {
+            buffer_view: 2,
+            byte_offset: 0,
+            component_type: UNSIGNED_SHORT,
+            count: index_count,
+            kind: "SCALAR",
+            min: None,
+            max: None,
+        },
+    ];
+
+    // baked cubes follow the shared one in the buffer, in a view of their own
+    let mut baked_length = 0;
+
+    for leaf in leaves {
+        let color_key = (leaf.color[0].to_bits(), leaf.color[1].to_bits(), leaf.color[2].to_bits());
+
+        let material = *material_by_color.entry(color_key).or_insert_with(|| {
+            materials.push(Material {
+                pbr_metallic_roughness: PbrMetallicRoughness {
+                    base_color_factor: [leaf.color[0], leaf.color[1], leaf.color[2], 1.0],
+                    metallic_factor: 0.0,
+                    roughness_factor: 1.0,
+                },
+            });
+
+            materials.len() - 1
+        });
+
+        let (mesh, matrix) = if is_trs(&leaf.transform) {
+            let mesh = *mesh_by_color.entry(color_key).or_insert_with(|| {
+                meshes.push(cube_mesh(0, 1, material));
+                meshes.len() - 1
+            });
+
+            (mesh, Some(leaf.transform.as_slice().to_vec()))
+        } else {
+            let (min, max) = write_baked_cube(&mut bin_writer, &leaf.transform)?;
+
+            accessors.push(Accessor {
+                buffer_view: 3,
+                byte_offset: baked_length,
+                component_type: FLOAT,
+                count: vertex_count,
+                kind: "VEC3",
+                min: Some(min.to_vec()),
+                max: Some(max.to_vec()),
+            });
+            accessors.push(Accessor {
+                buffer_view: 3,
+                byte_offset: baked_length + vec3_length,
+                component_type: FLOAT,
+                count: vertex_count,
+                kind: "VEC3",
+                min: None,
+                max: None,
+            });
+            baked_length += 2 * vec3_length;
+
+            meshes.push(cube_mesh(accessors.len() - 2, accessors.len() - 1, material));
+
+            (meshes.len() - 1, None)
+        };
+
+        let address = leaf.address.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(".");
+
+        nodes[0].children.push(nodes.len());
+        nodes.push(Node {
+            name: format!("leaf {}", address),
+            mesh: Some(mesh),
+            matrix,
+            children: Vec::new(),
+        });
+    }
+
+    let mut buffer_views = vec![
+        BufferView {
+            buffer: 0,
+            byte_offset: 0,
+            byte_length: vec3_length,
+            target: ARRAY_BUFFER,
+        },
+        BufferView {
+            buffer: 0,
+            byte_offset: vec3_length,
+            byte_length: vec3_length,
+            target: ARRAY_BUFFER,
+        },
+        BufferView {
+            buffer: 0,
+            byte_offset: 2 * vec3_length,
+            byte_length: index_length,
+            target: ELEMENT_ARRAY_BUFFER,
+        },
+    ];
+
+    // glTF doesn't allow empty buffer views
+    if baked_length > 0 {
+        buffer_views.push(BufferView {
+            buffer: 0,
+            byte_offset: 2 * vec3_length + index_length,
+            byte_length: baked_length,
+            target: ARRAY_BUFFER,
+        });
+    }
+
+    let gltf = Gltf {
+        asset: Asset {
+            version: "2.0",
+            generator: format!("{} {}", crate::NAME, crate::VERSION),
+        },
+        scene: 0,
+        scenes: vec![Scene { nodes: vec![0] }],
+        nodes,
+        meshes,
+        materials,
+        accessors,
+        buffer_views,
+        buffers: vec![Buffer {
+            uri: bin_uri.to_owned(),
+            byte_length: 2 * vec3_length + index_length + baked_length,
+        }],
+    };
+
+    serde_json::to_writer(writer, &gltf).map_err(io::Error::from)
+}
+
+/// A mesh drawing the cube whose vertices are in accessors `position` and `normal`, with the
+/// shared indices.
+fn cube_mesh(position: usize, normal: usize, material: usize) -> Mesh {
+    Mesh {
+        primitives: vec![Primitive {
+            attributes: Attributes { position, normal },
+            indices: 2,
+            material,
+        }],
+    }
+}
+
+/// Whether glTF accepts `transform` as a node matrix, i.e. whether its linear part is a rotation
+/// (possibly reflected) times a scale. That's the case exactly when its columns are orthogonal.
+fn is_trs(transform: &Matrix4<f32>) -> bool {
+    let linear = transform.fixed_slice::<U3, U3>(0, 0);
+
+    (0..3).all(|i| (i + 1..3).all(|j| {
+        let (a, b) = (linear.column(i), linear.column(j));
+        a.dot(&b).abs() <= ORTHOGONAL_TOLERANCE * a.norm() * b.norm()
+    }))
+}
+
+/// Write the unit cube mapped through `transform` in the same layout as the shared cube, so the
+/// shared indices draw it, and return the bounds of its positions.
+fn write_baked_cube<B: Write>(writer: &mut B, transform: &Matrix4<f32>) -> io::Result<([f32; 3], [f32; 3])> {
+    let linear = transform.fixed_slice::<U3, U3>(0, 0).into_owned();
+    let (corners, reflected) = geometry::cube_corners(transform);
+
+    // normals transform by the inverse transpose
+    let normal_transform = linear.try_inverse().map(|inverse| inverse.transpose()).unwrap_or_else(Matrix3::identity);
+
+    let mut min = [std::f32::INFINITY; 3];
+    let mut max = [std::f32::NEG_INFINITY; 3];
+
+    for face in UNIT_CUBE_FACES.iter() {
+        let mut face = *face;
+        if reflected {
+            face.reverse();
+        }
+
+        for &vertex in face.iter() {
+            let pt = corners[vertex];
+
+            for i in 0..3 {
+                min[i] = min[i].min(pt[i]);
+                max[i] = max[i].max(pt[i]);
+                write_f32(writer, pt[i])?;
+            }
+        }
+    }
+
+    for normal in UNIT_CUBE_NORMALS.iter() {
+        let normal = normal_transform * Vector3::new(normal[0], normal[1], normal[2]);
+        let normal = normal.try_normalize(1.0e-12).unwrap_or(normal);
+
+        for _ in 0..4 {
+            for &c in normal.iter() {
+                write_f32(writer, c)?;
+            }
+        }
+    }
+
+    Ok((min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifs::Leaf;
+    use na::{Matrix4, Vector3};
+    use super::write_gltf;
+
+    #[test]
+    fn sheared_leaves_are_baked() {
+        let leaf = |transform| Leaf {
+            address: vec![0],
+            transform,
+            color: Vector3::new(1.0, 0.0, 0.0),
+        };
+
+        let mut shear = Matrix4::identity();
+        shear[(0, 1)] = 0.5;
+
+        let (mut json, mut bin) = (Vec::new(), Vec::new());
+        write_gltf(&mut json, &mut bin, "export.bin", vec![leaf(Matrix4::new_scaling(-0.5)), leaf(shear)]).unwrap();
+
+        let gltf: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        assert!(gltf["nodes"][1]["matrix"].is_array());
+        assert!(gltf["nodes"][2]["matrix"].is_null());
+        assert_eq!(gltf["meshes"].as_array().map(Vec::len), Some(2));
+        assert_eq!(gltf["materials"].as_array().map(Vec::len), Some(1));
+        assert_eq!(gltf["buffers"][0]["byteLength"].as_u64(), Some(bin.len() as u64));
+    }
+}
@@ -0,0 +1,20 @@
+//! Writers for the leaves of an iterated scene in formats other tools understand.
+
+use std::io::{self, Write};
+
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8])
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8])
+}
+
+fn write_f32<W: Write>(writer: &mut W, value: f32) -> io::Result<()> {
+    write_u32(writer, value.to_bits())
+}
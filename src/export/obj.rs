@@ -0,0 +1,36 @@
+//! Wavefront OBJ output. Every leaf becomes its own group of eight vertices and six quads, with
+//! the leaf color attached to each vertex (`v x y z r g b`), which MeshLab and Blender both read.
+
+use crate::{
+    geometry::{self, UNIT_CUBE_FACES, UNIT_CUBE_VERTICES},
+    ifs::Leaf,
+};
+use std::io::{self, Write};
+
+pub fn write_obj<W: Write, I: IntoIterator<Item = Leaf>>(mut writer: W, leaves: I) -> io::Result<()> {
+    writeln!(writer, "# {} {}", crate::NAME, crate::VERSION)?;
+
+    for (idx, leaf) in leaves.into_iter().enumerate() {
+        let address = leaf.address.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("_");
+        writeln!(writer, "g leaf_{}", address)?;
+
+        let (corners, reflected) = geometry::cube_corners(&leaf.transform);
+
+        for pt in corners.iter() {
+            writeln!(writer, "v {} {} {} {} {} {}", pt[0], pt[1], pt[2], leaf.color[0], leaf.color[1], leaf.color[2])?;
+        }
+
+        let base = idx * UNIT_CUBE_VERTICES.len() + 1;
+
+        for face in UNIT_CUBE_FACES.iter() {
+            let mut face = *face;
+            if reflected {
+                face.reverse();
+            }
+
+            writeln!(writer, "f {} {} {} {}", base + face[0], base + face[1], base + face[2], base + face[3])?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! PLY point cloud output, with one colored vertex per point.
+
+use na::{Point3, Vector3};
+use std::io::{self, Write};
+use super::write_f32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// Write `count` points from `points` to `writer`. The count has to be known up front because it
+/// goes in the header; this lets point clouds far larger than memory be streamed straight to disk.
+pub fn write_ply<W, I>(mut writer: W, format: PlyFormat, count: usize, points: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item = (Point3<f32>, Vector3<f32>)>
+{
+    let format_name = match format {
+        PlyFormat::Ascii => "ascii",
+        PlyFormat::BinaryLittleEndian => "binary_little_endian",
+    };
+
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {} 1.0", format_name)?;
+    writeln!(writer, "comment generated by {} {}", crate::NAME, crate::VERSION)?;
+    writeln!(writer, "element vertex {}", count)?;
+    writeln!(writer, "property float x")?;
+    writeln!(writer, "property float y")?;
+    writeln!(writer, "property float z")?;
+    writeln!(writer, "property uchar red")?;
+    writeln!(writer, "property uchar green")?;
+    writeln!(writer, "property uchar blue")?;
+    writeln!(writer, "end_header")?;
+
+    let mut written = 0;
+
+    for (point, color) in points.into_iter().take(count) {
+        let rgb = [to_byte(color[0]), to_byte(color[1]), to_byte(color[2])];
+
+        match format {
+            PlyFormat::Ascii => {
+                writeln!(writer, "{} {} {} {} {} {}", point[0], point[1], point[2], rgb[0], rgb[1], rgb[2])?;
+            },
+
+            PlyFormat::BinaryLittleEndian => {
+                for i in 0..3 {
+                    write_f32(&mut writer, point[i])?;
+                }
+
+                writer.write_all(&rgb)?;
+            },
+        }
+
+        written += 1;
+    }
+
+    if written != count {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("expected {} points, got {}", count, written)));
+    }
+
+    Ok(())
+}
+
+fn to_byte(channel: f32) -> u8 {
+    (channel.max(0.0).min(1.0) * 255.0).round() as u8
+}
@@ -0,0 +1,284 @@
+//! Binary STL output of the union of all leaves.
+//!
+//! Leaves overlap freely, so writing them out as separate cubes produces something no slicer can
+//! print. Instead the leaves are rasterized into a voxel grid and only the faces separating filled
+//! voxels from empty ones are written. All vertices land on the grid lattice, so the surface is
+//! welded exactly; the only places it can fail to be manifold are edges where two voxels touch
+//! diagonally, and those are reported back to the caller.
+
+use alga::linear::Transformation;
+use crate::{
+    geometry,
+    ifs::Leaf,
+};
+use na::{Point3, Vector3};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+use super::{write_f32, write_u16, write_u32};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StlReport {
+    pub triangles: usize,
+
+    /// Edge length of a single voxel.
+    pub voxel_size: f32,
+
+    /// Edges of the surface not shared by exactly two faces.
+    pub non_manifold_edges: Vec<(Point3<f32>, Point3<f32>)>,
+}
+
+impl StlReport {
+    pub fn is_watertight(&self) -> bool {
+        self.non_manifold_edges.is_empty()
+    }
+}
+
+struct Grid {
+    origin: Point3<f32>,
+    voxel_size: f32,
+    dims: [usize; 3],
+    filled: Vec<bool>,
+}
+
+impl Grid {
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        (z * self.dims[1] + y) * self.dims[0] + x
+    }
+
+    /// Whether the voxel at the (possibly out-of-bounds) signed coordinate is filled.
+    fn get(&self, pos: [isize; 3]) -> bool {
+        if (0..3).any(|i| pos[i] < 0 || pos[i] >= self.dims[i] as isize) {
+            return false;
+        }
+
+        self.filled[self.index(pos[0] as usize, pos[1] as usize, pos[2] as usize)]
+    }
+
+    fn lattice_point(&self, pos: [usize; 3]) -> Point3<f32> {
+        self.origin + self.voxel_size * Vector3::new(pos[0] as f32, pos[1] as f32, pos[2] as f32)
+    }
+}
+
+/// Voxelize `leaves` with `resolution` voxels along the longest side of their bounding box and
+/// write the boundary of the result as binary STL.
+pub fn write_stl<W: Write>(mut writer: W, leaves: &[Leaf], resolution: usize) -> io::Result<StlReport> {
+    let grid = voxelize(leaves, resolution.max(1));
+    let quads = boundary(&grid);
+
+    let non_manifold_edges = non_manifold_edges(&quads)
+        .into_iter()
+        .map(|(p1, p2)| (grid.lattice_point(p1), grid.lattice_point(p2)))
+        .collect();
+
+    let mut header = [0u8; 80];
+    let title = format!("{} {} voxel union", crate::NAME, crate::VERSION);
+    header[..title.len()].copy_from_slice(title.as_bytes());
+
+    writer.write_all(&header)?;
+    write_u32(&mut writer, 2 * quads.len() as u32)?;
+
+    for (normal, corners) in quads.iter() {
+        let corners = corners.iter().map(|&c| grid.lattice_point(c)).collect::<Vec<_>>();
+
+        for tri in [[0, 1, 2], [0, 2, 3]].iter() {
+            for i in 0..3 {
+                write_f32(&mut writer, normal[i])?;
+            }
+
+            for &vertex in tri.iter() {
+                for i in 0..3 {
+                    write_f32(&mut writer, corners[vertex][i])?;
+                }
+            }
+
+            write_u16(&mut writer, 0)?;
+        }
+    }
+
+    Ok(StlReport {
+        triangles: 2 * quads.len(),
+        voxel_size: grid.voxel_size,
+        non_manifold_edges,
+    })
+}
+
+fn voxelize(leaves: &[Leaf], resolution: usize) -> Grid {
+    let corners = |leaf: &Leaf| geometry::cube_corners(&leaf.transform).0.to_vec();
+
+    let (min, max) = bounds(leaves.iter().flat_map(|leaf| corners(leaf)));
+    let extent = max - min;
+    let voxel_size = extent.iter().cloned().fold(0.0f32, f32::max) / resolution as f32;
+
+    if voxel_size <= 0.0 {
+        return Grid {
+            origin: min,
+            voxel_size: 1.0,
+            dims: [0, 0, 0],
+            filled: Vec::new(),
+        };
+    }
+
+    let mut dims = [0usize; 3];
+    for i in 0..3 {
+        dims[i] = ((extent[i] / voxel_size).ceil() as usize).max(1);
+    }
+
+    let mut grid = Grid {
+        origin: min,
+        voxel_size,
+        dims,
+        filled: vec![false; dims[0] * dims[1] * dims[2]],
+    };
+
+    for leaf in leaves.iter() {
+        // degenerate leaves have no volume to contribute
+        let inverse = match leaf.transform.try_inverse() {
+            Some(inverse) => inverse,
+            None => continue,
+        };
+
+        let (leaf_min, leaf_max) = bounds(corners(leaf).into_iter());
+
+        let mut lo = [0usize; 3];
+        let mut hi = [0usize; 3];
+        for i in 0..3 {
+            lo[i] = (((leaf_min[i] - min[i]) / voxel_size).floor().max(0.0) as usize).min(dims[i] - 1);
+            hi[i] = (((leaf_max[i] - min[i]) / voxel_size).ceil().max(0.0) as usize).min(dims[i]);
+        }
+
+        for z in lo[2]..hi[2] {
+            for y in lo[1]..hi[1] {
+                for x in lo[0]..hi[0] {
+                    let center = grid.lattice_point([x, y, z]) + Vector3::repeat(voxel_size / 2.0);
+                    let local = inverse.transform_point(&center);
+
+                    if local.coords.iter().all(|c| c.abs() <= 0.5) {
+                        let idx = grid.index(x, y, z);
+                        grid.filled[idx] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    grid
+}
+
+/// Every voxel face separating a filled voxel from an empty one, as an outward normal and four
+/// lattice corners wound counter-clockwise when seen from outside.
+fn boundary(grid: &Grid) -> Vec<(Vector3<f32>, [[usize; 3]; 4])> {
+    let mut quads = Vec::new();
+
+    for z in 0..grid.dims[2] {
+        for y in 0..grid.dims[1] {
+            for x in 0..grid.dims[0] {
+                if !grid.filled[grid.index(x, y, z)] {
+                    continue;
+                }
+
+                let pos = [x as isize, y as isize, z as isize];
+
+                for axis in 0..3 {
+                    // the other two axes, ordered so that (u, v, axis) is right-handed
+                    let u = (axis + 1) % 3;
+                    let v = (axis + 2) % 3;
+
+                    for &dir in [-1isize, 1].iter() {
+                        let mut neighbor = pos;
+                        neighbor[axis] += dir;
+
+                        if grid.get(neighbor) {
+                            continue;
+                        }
+
+                        let mut base = [x, y, z];
+                        if dir > 0 {
+                            base[axis] += 1;
+                        }
+
+                        let offset = |du: usize, dv: usize| {
+                            let mut corner = base;
+                            corner[u] += du;
+                            corner[v] += dv;
+                            corner
+                        };
+
+                        let corners = if dir > 0 {
+                            [offset(0, 0), offset(1, 0), offset(1, 1), offset(0, 1)]
+                        } else {
+                            [offset(0, 0), offset(0, 1), offset(1, 1), offset(1, 0)]
+                        };
+
+                        let mut normal = Vector3::zeros();
+                        normal[axis] = dir as f32;
+
+                        quads.push((normal, corners));
+                    }
+                }
+            }
+        }
+    }
+
+    quads
+}
+
+/// Lattice edges of the surface that aren't shared by exactly two quads.
+fn non_manifold_edges(quads: &[(Vector3<f32>, [[usize; 3]; 4])]) -> Vec<([usize; 3], [usize; 3])> {
+    let mut edge_counts: HashMap<([usize; 3], [usize; 3]), usize> = HashMap::new();
+
+    for (_, corners) in quads.iter() {
+        for i in 0..4 {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            let key = if a < b { (a, b) } else { (b, a) };
+
+            *edge_counts.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    let mut edges = edge_counts.into_iter()
+        .filter(|&(_, count)| count != 2)
+        .map(|(edge, _)| edge)
+        .collect::<Vec<_>>();
+
+    edges.sort();
+    edges
+}
+
+fn bounds<I: Iterator<Item = Point3<f32>>>(points: I) -> (Point3<f32>, Point3<f32>) {
+    use std::f32::{INFINITY, NEG_INFINITY};
+
+    points.fold(
+        (Point3::new(INFINITY, INFINITY, INFINITY), Point3::new(NEG_INFINITY, NEG_INFINITY, NEG_INFINITY)),
+        |(min, max), pt| (
+            Point3::new(min[0].min(pt[0]), min[1].min(pt[1]), min[2].min(pt[2])),
+            Point3::new(max[0].max(pt[0]), max[1].max(pt[1]), max[2].max(pt[2])),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ifs::Leaf;
+    use na::{Matrix4, Vector3};
+    use super::write_stl;
+
+    #[test]
+    fn single_cube_is_watertight() {
+        let leaf = Leaf {
+            address: vec![0],
+            transform: Matrix4::identity(),
+            color: Vector3::new(1.0, 1.0, 1.0),
+        };
+
+        let mut bytes = Vec::new();
+        let report = write_stl(&mut bytes, &[leaf], 4).unwrap();
+
+        assert!(report.is_watertight());
+
+        // a 4x4x4 block of voxels, with 16 faces on each side
+        assert_eq!(report.triangles, 2 * 6 * 16);
+        assert_eq!(bytes.len(), 80 + 4 + 50 * report.triangles);
+    }
+}
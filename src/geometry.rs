@@ -0,0 +1,77 @@
+//! Shared geometry for the unit cube every component and leaf is drawn as.
+
+use alga::linear::Transformation;
+use na::{Isometry3, Matrix4, Point3, U3, Vector3};
+use nc::{
+    query::{Ray3, RayCast, RayIntersection},
+    shape::Cuboid3,
+};
+
+/// Corners of the unit cube centered on the origin (the same cube kiss3d's `add_cube` draws).
+/// Bit 0 of the index selects +x, bit 1 +y and bit 2 +z.
+pub static UNIT_CUBE_VERTICES: [[f32; 3]; 8] = [
+    [-0.5, -0.5, -0.5],
+    [ 0.5, -0.5, -0.5],
+    [-0.5,  0.5, -0.5],
+    [ 0.5,  0.5, -0.5],
+    [-0.5, -0.5,  0.5],
+    [ 0.5, -0.5,  0.5],
+    [-0.5,  0.5,  0.5],
+    [ 0.5,  0.5,  0.5],
+];
+
+/// Faces of the unit cube as quads into `UNIT_CUBE_VERTICES`, wound counter-clockwise when seen
+/// from outside.
+pub static UNIT_CUBE_FACES: [[usize; 4]; 6] = [
+    [0, 4, 6, 2],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 6, 7, 3],
+    [0, 2, 3, 1],
+    [4, 5, 7, 6],
+];
+
+/// Outward normal of each face in `UNIT_CUBE_FACES`.
+pub static UNIT_CUBE_NORMALS: [[f32; 3]; 6] = [
+    [-1.0,  0.0,  0.0],
+    [ 1.0,  0.0,  0.0],
+    [ 0.0, -1.0,  0.0],
+    [ 0.0,  1.0,  0.0],
+    [ 0.0,  0.0, -1.0],
+    [ 0.0,  0.0,  1.0],
+];
+
+/// Corners of the unit cube mapped by `transform`, in the order of `UNIT_CUBE_VERTICES`, and
+/// whether `transform` reflects. A reflected cube is inside out, so anything that relies on the
+/// winding of `UNIT_CUBE_FACES` has to reverse it.
+pub fn cube_corners(transform: &Matrix4<f32>) -> ([Point3<f32>; 8], bool) {
+    let mut corners = [Point3::origin(); 8];
+
+    for (corner, v) in corners.iter_mut().zip(UNIT_CUBE_VERTICES.iter()) {
+        *corner = transform.transform_point(&Point3::new(v[0], v[1], v[2]));
+    }
+
+    let reflected = transform.fixed_slice::<U3, U3>(0, 0).determinant() < 0.0;
+
+    (corners, reflected)
+}
+
+/// Cast `ray` against the unit cube mapped by the affine transform whose inverse is `inverse`.
+///
+/// The ray is carried into the cube's local frame, where ncollide can treat it as an axis-aligned
+/// box, so this works for any invertible transform including shear and reflections. Since the
+/// ray's direction isn't renormalized, the time of impact is the same in both frames. The normal
+/// is brought back to world space and normalized.
+pub fn cast_unit_cube(inverse: &Matrix4<f32>, ray: &Ray3<f32>, solid: bool) -> Option<RayIntersection<Vector3<f32>>> {
+    let local_ray = Ray3::new(inverse.transform_point(&ray.origin), inverse.transform_vector(&ray.dir));
+
+    Cuboid3::new(Vector3::repeat(0.5))
+        .toi_and_normal_with_ray(&Isometry3::identity(), &local_ray, solid)
+        .map(|mut intersection| {
+            // normals transform by the inverse transpose
+            let normal = inverse.transpose().transform_vector(&intersection.normal);
+            intersection.normal = normal.try_normalize(1.0e-12).unwrap_or(normal);
+
+            intersection
+        })
+}
@@ -0,0 +1,62 @@
+//! Bounding volumes for the whole attractor, so subtrees can be culled without expanding them.
+
+use alga::linear::Transformation;
+use crate::geometry::UNIT_CUBE_VERTICES;
+use na::{Matrix4, Point3, Vector3, U3};
+use super::System;
+
+/// Largest factor by which `transform` stretches any distance: the spectral norm of its linear
+/// part.
+pub fn lipschitz(transform: &Matrix4<f32>) -> f32 {
+    transform.fixed_slice::<U3, U3>(0, 0)
+        .into_owned()
+        .svd(false, false)
+        .singular_values
+        .iter()
+        .fold(0.0f32, |max, &s| max.max(s))
+}
+
+/// A ball containing the unit cube which every map of a system takes into itself. Its image under
+/// a node's composite transform therefore contains that node's whole subtree, and its image under
+/// a leaf's transform contains the leaf's cube.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBall {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl System {
+    /// The bounding ball of this system, or `None` if some map isn't a contraction (in which case
+    /// the attractor may be unbounded).
+    pub fn bounding_ball(&self) -> Option<BoundingBall> {
+        if self.maps.is_empty() {
+            return None;
+        }
+
+        // any center works; the mean of the maps' translations sits near the attractor
+        let center = Point3::from_coordinates(
+            self.maps.iter()
+                .map(|map| map.transform.transform_point(&Point3::origin()).coords)
+                .fold(Vector3::zeros(), |acc, v| acc + v) / self.maps.len() as f32
+        );
+
+        let cube_radius = UNIT_CUBE_VERTICES.iter()
+            .map(|v| (Point3::new(v[0], v[1], v[2]) - center).norm())
+            .fold(0.0f32, f32::max);
+
+        // f(B) lies within |f(c) - c| + L(f) r of c, so B contains it once r is at least
+        // |f(c) - c| / (1 - L(f))
+        self.maps.iter()
+            .map(|map| {
+                let contraction = lipschitz(&map.transform);
+
+                if contraction < 1.0 {
+                    Some((map.transform.transform_point(&center) - center).norm() / (1.0 - contraction))
+                } else {
+                    None
+                }
+            })
+            .fold(Some(cube_radius), |radius, map_radius| Some(radius?.max(map_radius?)))
+            .map(|radius| BoundingBall { center, radius })
+    }
+}
@@ -0,0 +1,121 @@
+//! The chaos game: a random walk through the maps of a system whose trail approximates its
+//! attractor at effectively unlimited depth.
+
+use alga::linear::Transformation;
+use na::{Point3, Vector3};
+use rand::{
+    prng::XorShiftRng,
+    Rng,
+    SeedableRng,
+};
+use super::System;
+
+/// Iterations discarded before the walk is considered to be on the attractor.
+pub const WARMUP_ITERATIONS: usize = 32;
+
+/// Seed used by `System::chaos_game`, so repeated runs produce identical point sets.
+const SEED: [u8; 16] = *b"3frac chaos game";
+
+/// Infinite iterator of points on the attractor and their colors. See `System::chaos_game`.
+pub struct ChaosGame<'a, R: Rng> {
+    system: &'a System,
+    rng: R,
+
+    /// Running sum of the map weights, for sampling.
+    cumulative: Vec<f32>,
+
+    point: Point3<f32>,
+    color: Vector3<f32>,
+}
+
+impl System {
+    /// Normalized probability of picking each map. Maps without an explicit weight get one
+    /// proportional to the volume they scale by, which spreads points evenly over the attractor
+    /// when the maps don't overlap.
+    pub fn probabilities(&self) -> Vec<f32> {
+        let weights = self.maps.iter()
+            .map(|map| map.weight.unwrap_or_else(|| super::volume(&map.transform)).max(0.0))
+            .collect::<Vec<_>>();
+
+        let total = weights.iter().sum::<f32>();
+
+        // with no usable weights, fall back to picking maps uniformly
+        if total <= 0.0 || !total.is_finite() {
+            return vec![1.0 / self.maps.len() as f32; self.maps.len()];
+        }
+
+        weights.into_iter().map(|w| w / total).collect()
+    }
+
+    /// Play the chaos game with a fixed seed.
+    pub fn chaos_game(&self) -> ChaosGame<XorShiftRng> {
+        self.chaos_game_with_rng(XorShiftRng::from_seed(SEED))
+    }
+
+    /// Play the chaos game with the `stream`th of a family of fixed seeds. Distinct streams give
+    /// independent walks, so a long run can be split across threads and still come out the same
+    /// every time.
+    pub fn chaos_stream(&self, stream: u64) -> ChaosGame<XorShiftRng> {
+        let mut seed = SEED;
+
+        (0..8).for_each(|i| seed[i] ^= (stream >> (8 * i)) as u8);
+
+        self.chaos_game_with_rng(XorShiftRng::from_seed(seed))
+    }
+
+    pub fn chaos_game_with_rng<R: Rng>(&self, rng: R) -> ChaosGame<R> {
+        let cumulative = self.probabilities()
+            .into_iter()
+            .scan(0.0, |acc, p| {
+                *acc += p;
+                Some(*acc)
+            })
+            .collect();
+
+        let mut game = ChaosGame {
+            system: self,
+            rng,
+            cumulative,
+            point: Point3::origin(),
+            color: self.maps.first().map_or(Vector3::zeros(), |map| map.color),
+        };
+
+        for _ in 0..WARMUP_ITERATIONS {
+            if game.step().is_none() {
+                break;
+            }
+        }
+
+        game
+    }
+}
+
+impl<'a, R: Rng> ChaosGame<'a, R> {
+    fn step(&mut self) -> Option<(Point3<f32>, Vector3<f32>)> {
+        if self.cumulative.is_empty() {
+            return None;
+        }
+
+        let sample = self.rng.gen::<f32>() * self.cumulative[self.cumulative.len() - 1];
+        let idx = self.cumulative.iter()
+            .position(|&c| sample < c)
+            .unwrap_or(self.cumulative.len() - 1);
+
+        let map = &self.system.maps[idx];
+
+        // colors average along the walk, so each point is tinted by the maps that led to it,
+        // most recent first
+        self.point = map.transform.transform_point(&self.point);
+        self.color = (self.color + map.color) / 2.0;
+
+        Some((self.point, self.color))
+    }
+}
+
+impl<'a, R: Rng> Iterator for ChaosGame<'a, R> {
+    type Item = (Point3<f32>, Vector3<f32>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step()
+    }
+}
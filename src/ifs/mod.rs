@@ -0,0 +1,381 @@
+//! Window-independent expansion of the iterated function system described by the scene.
+//!
+//! Everything here is pure math over `nalgebra` types, so the viewer, exporters and any
+//! offline tooling all see exactly the same leaves.
+
+use alga::linear::Transformation;
+use na::{
+    Matrix4,
+    Point3,
+    Vector3,
+};
+use palette::{Hsl, LinSrgb, RgbHue};
+
+pub use self::bounds::{lipschitz, BoundingBall};
+pub use self::chaos::ChaosGame;
+pub use self::refine::volume;
+
+mod bounds;
+mod chaos;
+mod refine;
+
+/// Target number of leaves per subtree when splitting work across threads.
+pub const PARTITION_LEAVES: usize = 4_096;
+
+/// One affine map of the system, along with the color it contributes to its leaves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Map {
+    pub transform: Matrix4<f32>,
+    pub color: Vector3<f32>,
+
+    /// Relative probability of this map in stochastic rendering. `None` means proportional to the
+    /// volume the map scales by.
+    pub weight: Option<f32>,
+}
+
+/// A leaf of the expansion tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Leaf {
+    /// Indices of the maps composed to produce this leaf, outermost first.
+    pub address: Vec<usize>,
+
+    /// The composite transform `M[address[0]] * M[address[1]] * ... * M[address[n]]`.
+    pub transform: Matrix4<f32>,
+
+    pub color: Vector3<f32>,
+}
+
+impl Leaf {
+    /// Image of the origin under this leaf, i.e. the center of its cube.
+    pub fn center(&self) -> Point3<f32> {
+        self.transform.transform_point(&Point3::origin())
+    }
+}
+
+/// How leaves are colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Coloring {
+    /// Circular mean of the hues of the maps along the leaf's address.
+    Hue,
+
+    /// Heat map of the leaf's measure `p[address[0]] * ... * p[address[n]]`, from blue for the
+    /// least likely leaves to red for the most likely.
+    Measure,
+}
+
+impl Default for Coloring {
+    fn default() -> Self {
+        Coloring::Hue
+    }
+}
+
+/// What a leaf's color is computed from, accumulated along its address.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Tint {
+    /// Sum of the hues along the address as points on the unit circle.
+    hue_y: f32,
+    hue_x: f32,
+
+    /// Log of the product of the probabilities along the address.
+    log_measure: f32,
+
+    /// Length of the address.
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct System {
+    maps: Vec<Map>,
+
+    /// Hue of each map as a point on the unit circle `(sin, cos)`, so hues can be averaged.
+    hues: Vec<(f32, f32)>,
+
+    /// Natural log of each map's normalized probability.
+    log_probabilities: Vec<f32>,
+
+    /// Smallest and largest finite entries of `log_probabilities`.
+    log_probability_range: (f32, f32),
+
+    coloring: Coloring,
+    saturation: f32,
+    lightness: f32,
+}
+
+impl System {
+    pub fn new(maps: Vec<Map>) -> Self {
+        // leaves borrow saturation and lightness from the first map; only hue is blended
+        let (saturation, lightness) = maps.first()
+            .map(|map| {
+                let hsl = to_hsl(&map.color);
+                (hsl.saturation, hsl.lightness)
+            })
+            .unwrap_or((1.0, 0.5));
+
+        let hues = maps.iter()
+            .map(|map| {
+                let hue_angle = to_hsl(&map.color).hue.to_positive_radians();
+                (hue_angle.sin(), hue_angle.cos())
+            })
+            .collect();
+
+        let mut system = System {
+            maps,
+            hues,
+            log_probabilities: Vec::new(),
+            log_probability_range: (0.0, 0.0),
+            coloring: Coloring::default(),
+            saturation,
+            lightness,
+        };
+
+        system.log_probabilities = system.probabilities().into_iter().map(f32::ln).collect();
+        system.log_probability_range = system.log_probabilities.iter()
+            .filter(|p| p.is_finite())
+            .fold((std::f32::INFINITY, std::f32::NEG_INFINITY), |(min, max), &p| (min.min(p), max.max(p)));
+
+        system
+    }
+
+    pub fn with_coloring(mut self, coloring: Coloring) -> Self {
+        self.coloring = coloring;
+        self
+    }
+
+    pub fn maps(&self) -> &[Map] {
+        &self.maps
+    }
+
+    /// Number of leaves produced at the given depth, or `None` if that doesn't fit in a `usize`.
+    pub fn leaf_count(&self, depth: usize) -> Option<usize> {
+        self.maps.len().checked_pow(depth as u32 + 1)
+    }
+
+    /// Enumerate every leaf at `depth`, i.e. every composition of `depth + 1` maps, in
+    /// lexicographic order of address.
+    ///
+    /// Leaves are produced lazily by a depth-first walk that reuses the product of each parent's
+    /// maps, so every node of the tree costs a single matrix multiply. Dropping the iterator early
+    /// stops the walk.
+    pub fn leaves(&self, depth: usize) -> Leaves {
+        self.subtree(&[], depth)
+    }
+
+    /// Enumerate the leaves at `depth` whose address starts with `prefix`, in the same order
+    /// `leaves` would produce them.
+    pub fn subtree(&self, prefix: &[usize], depth: usize) -> Leaves {
+        assert!(prefix.len() <= depth + 1, "prefix is longer than a leaf address");
+
+        let mut address = prefix.to_vec();
+        address.resize(depth + 1, 0);
+
+        Leaves {
+            system: self,
+            address,
+            prefixes: vec![(Matrix4::identity(), Tint::default()); depth + 2],
+            fixed: prefix.len(),
+            valid: 1,
+            done: self.maps.is_empty(),
+        }
+    }
+
+    /// Split the leaves at `depth` into subtrees of at most `max_leaves` leaves each (as long as
+    /// that's possible), returned as address prefixes in lexicographic order.
+    pub fn partitions(&self, depth: usize, max_leaves: usize) -> Vec<Vec<usize>> {
+        use itertools::Itertools;
+
+        if self.maps.is_empty() {
+            return Vec::new();
+        }
+
+        // subtrees too big to count certainly don't fit
+        let prefix_len = (0..depth + 1)
+            .find(|&len| {
+                self.maps.len()
+                    .checked_pow((depth + 1 - len) as u32)
+                    .map_or(false, |count| count <= max_leaves)
+            })
+            .unwrap_or(depth + 1);
+
+        if prefix_len == 0 {
+            return vec![Vec::new()];
+        }
+
+        (0..prefix_len)
+            .map(|_| 0..self.maps.len())
+            .multi_cartesian_product()
+            .collect()
+    }
+
+    /// Every leaf at `depth`, generated in parallel across subtrees. The result is identical
+    /// (bit for bit, and in order) to collecting `leaves`, whatever the number of threads.
+    pub fn par_leaves(&self, depth: usize) -> Vec<Leaf> {
+        use rayon::prelude::*;
+
+        self.partitions(depth, PARTITION_LEAVES)
+            .par_iter()
+            .map(|prefix| self.subtree(prefix, depth).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|leaves| leaves)
+            .collect()
+    }
+
+    /// Color of the leaf at `address`.
+    pub fn blend<I: IntoIterator<Item = usize>>(&self, address: I) -> Vector3<f32> {
+        let tint = address.into_iter().fold(Tint::default(), |tint, i| self.tint(tint, i));
+
+        self.color(tint)
+    }
+
+    /// `parent` extended by map `idx`.
+    fn tint(&self, parent: Tint, idx: usize) -> Tint {
+        Tint {
+            hue_y: parent.hue_y + self.hues[idx].0,
+            hue_x: parent.hue_x + self.hues[idx].1,
+            log_measure: parent.log_measure + self.log_probabilities[idx],
+            len: parent.len + 1,
+        }
+    }
+
+    fn color(&self, tint: Tint) -> Vector3<f32> {
+        let hue = match self.coloring {
+            Coloring::Hue => RgbHue::from_radians(tint.hue_y.atan2(tint.hue_x)),
+
+            Coloring::Measure => {
+                // compare the average log probability per level against the range it can take;
+                // leaves of zero measure land at the cold end
+                let (min, max) = self.log_probability_range;
+
+                let t = if max > min && tint.len > 0 {
+                    ((tint.log_measure / tint.len as f32 - min) / (max - min)).max(0.0).min(1.0)
+                } else {
+                    0.5
+                };
+
+                RgbHue::from_degrees(240.0 * (1.0 - t))
+            },
+        };
+
+        let hsl = Hsl::new(hue, self.saturation, self.lightness);
+        let rgb: LinSrgb<_> = hsl.into();
+
+        Vector3::new(rgb.red, rgb.green, rgb.blue)
+    }
+}
+
+/// Streaming depth-first iterator over the leaves of a `System`. See `System::leaves`.
+pub struct Leaves<'a> {
+    system: &'a System,
+
+    /// Address of the next leaf to yield.
+    address: Vec<usize>,
+
+    /// `prefixes[k]` is the composite transform and tint of `address[..k]`.
+    prefixes: Vec<(Matrix4<f32>, Tint)>,
+
+    /// Number of leading entries of `address` that never change.
+    fixed: usize,
+
+    /// Number of entries of `prefixes` that are up to date with `address`.
+    valid: usize,
+
+    done: bool,
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = Leaf;
+
+    fn next(&mut self) -> Option<Leaf> {
+        if self.done {
+            return None;
+        }
+
+        let system = self.system;
+        let maps = &system.maps;
+
+        for k in self.valid..self.prefixes.len() {
+            let idx = self.address[k - 1];
+            let (parent_transform, parent_tint) = self.prefixes[k - 1];
+
+            self.prefixes[k] = (parent_transform * maps[idx].transform, system.tint(parent_tint, idx));
+        }
+
+        let (transform, tint) = self.prefixes[self.prefixes.len() - 1];
+        let leaf = Leaf {
+            address: self.address.clone(),
+            transform,
+            color: system.color(tint),
+        };
+
+        // advance the address like an odometer; everything before the digit that rolls over
+        // keeps its prefix
+        match self.address[self.fixed..].iter().rposition(|&i| i + 1 < maps.len()).map(|pos| pos + self.fixed) {
+            Some(pos) => {
+                self.address[pos] += 1;
+                self.address[pos + 1..].iter_mut().for_each(|i| *i = 0);
+                self.valid = pos + 1;
+            },
+            None => self.done = true,
+        }
+
+        Some(leaf)
+    }
+}
+
+fn to_hsl(color: &Vector3<f32>) -> Hsl {
+    LinSrgb::new(color[0], color[1], color[2]).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Sierpinski-like system of three half-size maps with distinct hues.
+    pub fn triangle() -> System {
+        let corners = [Vector3::new(0.0, 0.5, 0.0), Vector3::new(-0.5, -0.5, 0.0), Vector3::new(0.5, -0.5, 0.3)];
+        let colors = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
+
+        let maps = corners.iter()
+            .zip(colors.iter())
+            .map(|(corner, color)| Map {
+                transform: Matrix4::new_translation(corner) * Matrix4::new_scaling(0.5),
+                color: *color,
+                weight: None,
+            })
+            .collect();
+
+        System::new(maps)
+    }
+
+    #[test]
+    fn leaves_match_cartesian_product() {
+        use itertools::Itertools;
+
+        let system = triangle();
+        let depth = 3;
+
+        // the straightforward expansion: multiply out every address from scratch
+        let expected = (0..depth + 1)
+            .map(|_| 0..system.maps().len())
+            .multi_cartesian_product()
+            .map(|address| Leaf {
+                transform: address.iter().map(|&i| system.maps()[i].transform).product(),
+                color: system.blend(address.iter().cloned()),
+                address,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(system.leaves(depth).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn par_leaves_matches_leaves() {
+        let system = triangle();
+
+        // deep enough to be split into several partitions
+        let depth = 8;
+        assert!(system.leaf_count(depth).unwrap() > PARTITION_LEAVES);
+
+        assert_eq!(system.par_leaves(depth), system.leaves(depth).collect::<Vec<_>>());
+    }
+}
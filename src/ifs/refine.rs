@@ -0,0 +1,148 @@
+//! Best-first refinement of the expansion tree under a leaf budget.
+
+use na::{Matrix4, U3};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::atomic::{AtomicBool, Ordering as AtomicOrdering},
+};
+use super::{Leaf, System, Tint};
+
+/// A node of the expansion tree that hasn't been subdivided (yet).
+struct Cell {
+    priority: f32,
+    address: Vec<usize>,
+    transform: Matrix4<f32>,
+    tint: Tint,
+}
+
+impl PartialEq for Cell {
+    fn eq(&self, other: &Cell) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Cell {}
+
+impl PartialOrd for Cell {
+    fn partial_cmp(&self, other: &Cell) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cell {
+    // highest priority first; ties go to the lowest address so the result never depends on the
+    // heap's internal order
+    fn cmp(&self, other: &Cell) -> Ordering {
+        self.priority.partial_cmp(&other.priority)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.address.cmp(&self.address))
+    }
+}
+
+/// Volume of the unit cube under `transform`, the default refinement priority.
+pub fn volume(transform: &Matrix4<f32>) -> f32 {
+    transform.fixed_slice::<U3, U3>(0, 0).determinant().abs()
+}
+
+impl System {
+    /// Expand the tree best-first: starting from the maps themselves, keep replacing the cell with
+    /// the highest `priority` by its children until that would take the total past `budget` leaves,
+    /// or every remaining cell is either at `max_depth` or has a priority below `min_priority`.
+    /// Leaves are returned in address order.
+    ///
+    /// With a budget of at least `leaf_count(max_depth)` leaves and no minimum priority, this is
+    /// the same set of leaves as `leaves(max_depth)`.
+    ///
+    /// `cancelled` is checked as the tree is expanded; once it's set, this gives up and returns
+    /// `None`.
+    pub fn refine<F>(&self, max_depth: usize, budget: usize, min_priority: f32, priority: F, cancelled: &AtomicBool) -> Option<Vec<Leaf>>
+        where F: Fn(&Matrix4<f32>) -> f32
+    {
+        let n = self.maps.len();
+
+        let mut open = (0..n)
+            .map(|i| self.cell(&priority, Vec::new(), Matrix4::identity(), Tint::default(), i))
+            .collect::<BinaryHeap<_>>();
+
+        let mut finished = Vec::new();
+
+        while let Some(cell) = open.pop() {
+            if cancelled.load(AtomicOrdering::Relaxed) {
+                return None;
+            }
+
+            // cells come out in priority order, so everything left is below the minimum too
+            if cell.priority < min_priority {
+                finished.push(cell);
+                break;
+            }
+
+            if cell.address.len() > max_depth {
+                finished.push(cell);
+                continue;
+            }
+
+            // subdividing a cell costs n - 1 leaves, the same for every cell, so once one doesn't
+            // fit nothing will
+            if open.len() + finished.len() + n > budget {
+                finished.push(cell);
+                break;
+            }
+
+            for i in 0..n {
+                open.push(self.cell(&priority, cell.address.clone(), cell.transform, cell.tint, i));
+            }
+        }
+
+        finished.extend(open.into_iter());
+        finished.sort_by(|a, b| a.address.cmp(&b.address));
+
+        let leaves = finished.into_iter()
+            .map(|cell| Leaf {
+                color: self.color(cell.tint),
+                address: cell.address,
+                transform: cell.transform,
+            })
+            .collect();
+
+        Some(leaves)
+    }
+
+    fn cell<F: Fn(&Matrix4<f32>) -> f32>(&self, priority: &F, mut address: Vec<usize>, parent: Matrix4<f32>, tint: Tint, idx: usize) -> Cell {
+        let transform = parent * self.maps[idx].transform;
+        address.push(idx);
+
+        Cell {
+            priority: priority(&transform),
+            address,
+            transform,
+            tint: self.tint(tint, idx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use super::super::tests::triangle;
+    use super::volume;
+
+    #[test]
+    fn unlimited_refine_matches_leaves() {
+        let system = triangle();
+        let depth = 4;
+
+        let refined = system.refine(depth, usize::max_value(), 0.0, volume, &AtomicBool::new(false))
+            .expect("refinement wasn't cancelled");
+
+        assert_eq!(refined, system.leaves(depth).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cancelled_refine_gives_up() {
+        let system = triangle();
+
+        assert_eq!(system.refine(4, usize::max_value(), 0.0, volume, &AtomicBool::new(true)), None);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     COLOR_ADJUST_BASE,
     COLOR_ADJUST_FINE,
-    state::State,
+    state::{Edit, State},
 };
 use glfw::{Action, Key, Modifiers, WindowEvent};
 use std::collections::HashSet;
@@ -21,12 +21,9 @@ pub fn color(state: &mut State, event: &mut WindowEvent) -> InputResult {
 
             event.inhibited = true;
 
-            let comp = state.selection().unwrap();
+            state.begin_edit(Edit::Adjust("color"));
 
             let offset = offset as f32;
-            let mut comp = comp.borrow_mut();
-
-            let mut color: Hsl<_, _> = LinSrgb::new(comp.color[0], comp.color[1], comp.color[2]).into();
 
             let adjustment = if fine {
                 COLOR_ADJUST_BASE * COLOR_ADJUST_FINE
@@ -34,11 +31,17 @@ pub fn color(state: &mut State, event: &mut WindowEvent) -> InputResult {
                 COLOR_ADJUST_BASE
             };
 
-            color.hue = color.hue + adjustment * offset;
+            // every selected box turns by the same amount, keeping the hues' relative spacing
+            state.selected().iter().for_each(|comp| {
+                let mut comp = comp.borrow_mut();
+
+                let mut color: Hsl<_, _> = LinSrgb::new(comp.color[0], comp.color[1], comp.color[2]).into();
+                color.hue = color.hue + adjustment * offset;
 
-            let color = LinSrgb::from_hsl(color);
+                let color = LinSrgb::from_hsl(color);
 
-            comp.color = Vector3::new(color.red, color.green, color.blue);
+                comp.color = Vector3::new(color.red, color.green, color.blue);
+            });
 
             InputResult::Handled
         },
@@ -1,9 +1,11 @@
 use crate::{
-    component::Component,
-    state::State,
+    component::ComponentSpec,
+    DUPLICATE_OFFSET,
+    state::{Edit, State},
     state::world::DragState,
 };
 use glfw::{Action, Key, Modifiers, WindowEvent};
+use na::Vector3;
 use std::cell::RefCell;
 use std::rc::Rc;
 use super::InputResult;
@@ -32,14 +34,15 @@ pub fn create(state: &mut State, event: &mut WindowEvent) -> InputResult {
 
             let intersect = loc + toi * dir;
 
-            let mut new_component = Component::new(state.root_group());
+            let mut new_component = ComponentSpec::default();
             new_component.origin = intersect.coords;
 
-            if !(mods & Modifiers::Shift).is_empty() {
+            if !(*mods & Modifiers::Shift).is_empty() {
                 new_component.scale *= 2.0;
             }
 
-            components.push(Rc::new(RefCell::new(new_component)));
+            state.begin_edit(Edit::Discrete);
+            state.new_component(new_component);
 
             InputResult::Handled
         },
@@ -65,5 +68,47 @@ pub fn delete(state: &mut State, event: &mut WindowEvent) -> InputResult {
             InputResult::Handled
         },
 
+        _ => InputResult::Continue,
+    }
+}
+
+
+/// `Ctrl-D` duplicates the selection, offset slightly so the copies are visible.
+pub fn duplicate(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::D, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            state.duplicate_selected(&Vector3::repeat(DUPLICATE_OFFSET));
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+/// `Ctrl-C` copies the selection; `Ctrl-V` pastes it back in place.
+pub fn copy_paste(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::C, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if let Err(e) = state.copy_selected() {
+                eprintln!("failed to copy selection: {}", e);
+            }
+
+            InputResult::Handled
+        },
+
+        Key(Key::V, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if let Err(e) = state.paste() {
+                eprintln!("clipboard doesn't hold any boxes: {}", e);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
     }
 }
@@ -0,0 +1,131 @@
+use crate::{
+    CHAOS_EXPORT_POINTS,
+    export::ply::PlyFormat,
+    GLTF_EXPORT_PATH,
+    HISTOGRAM_POINTS,
+    HISTOGRAM_RENDER_PATH,
+    OBJ_EXPORT_PATH,
+    PLY_EXPORT_PATH,
+    PLY_EXTRA_DEPTH,
+    PLY_MAX_POINTS,
+    RENDER_SCALE,
+    state::State,
+    STL_EXPORT_PATH,
+    TRACE_RENDER_PATH,
+};
+use glfw::{Action, Key, Modifiers, WindowEvent};
+use super::InputResult;
+
+pub fn export_obj(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::E, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if let Err(e) = state.export_obj(OBJ_EXPORT_PATH) {
+                eprintln!("failed to export {}: {}", OBJ_EXPORT_PATH, e);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn export_stl(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::T, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            match state.export_stl(STL_EXPORT_PATH) {
+                Ok(ref report) if !report.is_watertight() => {
+                    eprintln!("{} is not watertight: {} non-manifold edges", STL_EXPORT_PATH, report.non_manifold_edges.len());
+
+                    report.non_manifold_edges.iter().for_each(|(p1, p2)| eprintln!("  {} -> {}", p1, p2));
+                },
+                Ok(_) => {},
+                Err(e) => eprintln!("failed to export {}: {}", STL_EXPORT_PATH, e),
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn export_ply(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::P, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            let format = if (*mods & Modifiers::Shift).is_empty() {
+                PlyFormat::BinaryLittleEndian
+            } else {
+                PlyFormat::Ascii
+            };
+
+            let result = if state.is_chaos() {
+                state.export_chaos_points(PLY_EXPORT_PATH, CHAOS_EXPORT_POINTS, format)
+            } else {
+                // go as many levels deeper as the point budget allows
+                let system = state.system();
+                let base = state.iteration_depth();
+                let depth = (base..base + PLY_EXTRA_DEPTH + 1)
+                    .rev()
+                    .find(|&depth| system.leaf_count(depth).map_or(false, |count| count <= PLY_MAX_POINTS))
+                    .unwrap_or(base);
+
+                state.export_points(PLY_EXPORT_PATH, depth, format)
+            };
+
+            if let Err(e) = result {
+                eprintln!("failed to export {}: {}", PLY_EXPORT_PATH, e);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn export_gltf(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::G, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if let Err(e) = state.export_gltf(GLTF_EXPORT_PATH) {
+                eprintln!("failed to export {}: {}", GLTF_EXPORT_PATH, e);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn render_histogram(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::R, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() && (*mods & Modifiers::Shift).is_empty() => {
+            if let Err(e) = state.render_histogram(HISTOGRAM_RENDER_PATH, HISTOGRAM_POINTS, RENDER_SCALE) {
+                eprintln!("failed to render {}: {}", HISTOGRAM_RENDER_PATH, e);
+            }
+
+            InputResult::Handled
+        },
+
+        Key(Key::R, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if let Err(e) = state.render_trace(TRACE_RENDER_PATH, RENDER_SCALE) {
+                eprintln!("failed to render {}: {}", TRACE_RENDER_PATH, e);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
@@ -0,0 +1,22 @@
+use crate::state::State;
+use glfw::{Action, Key, Modifiers, WindowEvent};
+use super::InputResult;
+
+/// `Ctrl-Z` undoes the last edit, `Ctrl-Shift-Z` redoes it.
+pub fn undo(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::Z, _, action, mods) if *action != Action::Release && !(*mods & Modifiers::Control).is_empty() => {
+            if (*mods & Modifiers::Shift).is_empty() {
+                state.undo();
+            } else {
+                state.redo();
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
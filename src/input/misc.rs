@@ -1,5 +1,5 @@
 use crate::{
-    component::Component,
+    component::ComponentSpec,
     state::State,
     state::world::DragState,
 };
@@ -30,10 +30,60 @@ pub fn increase_depth(state: &mut State, event: &mut WindowEvent) -> InputResult
 
 pub fn decrease_depth(state: &mut State, event: &mut WindowEvent) -> InputResult {
     match event {
-        Key(Key::Right, _, Action::Press, _) => {
+        Key(Key::Left, _, Action::Press, _) => {
             state.decrease_depth();
             InputResult::Handled
         },
         _ => InputResult::Continue,
     }
-}
\ No newline at end of file
+}
+
+pub fn toggle_chaos(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    match event {
+        Key(Key::G, _, Action::Press, mods) if (*mods & Modifiers::Control).is_empty() => {
+            state.toggle_chaos();
+            InputResult::Handled
+        },
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn toggle_adaptive(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    match event {
+        Key(Key::V, _, Action::Press, mods) if (*mods & Modifiers::Control).is_empty() => {
+            state.toggle_adaptive();
+            InputResult::Handled
+        },
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn toggle_coloring(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    match event {
+        Key(Key::M, _, Action::Press, mods) if (*mods & Modifiers::Control).is_empty() => {
+            state.toggle_coloring();
+            InputResult::Handled
+        },
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn increase_budget(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    match event {
+        Key(Key::RightBracket, _, Action::Press, _) => {
+            state.increase_budget();
+            InputResult::Handled
+        },
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn decrease_budget(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    match event {
+        Key(Key::LeftBracket, _, Action::Press, _) => {
+            state.decrease_budget();
+            InputResult::Handled
+        },
+        _ => InputResult::Continue,
+    }
+}
@@ -14,14 +14,68 @@ use std::collections::HashSet;
 mod translation;
 mod rotation;
 mod scale;
+mod shear;
 mod color;
+mod weight;
 mod camera;
 mod component_lifecycle;
 mod selection;
 mod misc;
+mod export;
+mod persistence;
+mod history;
 
-pub fn process_input(state: &mut State, event: &mut WindowEvent) {
+type Handler = fn(&mut State, &mut WindowEvent) -> InputResult;
+
+/// Every input handler, in the order they're offered each event. Modified shortcuts (`Ctrl`, `Alt`)
+/// come before the plain keys they share, and camera zoom comes last so it only gets scrolls no
+/// held key claimed.
+static HANDLERS: &[Handler] = &[
+    persistence::save,
+    persistence::load,
+    export::export_obj,
+    export::export_stl,
+    export::export_ply,
+    export::export_gltf,
+    export::render_histogram,
+    history::undo,
+    component_lifecycle::duplicate,
+    component_lifecycle::copy_paste,
+    weight::reset_weights,
+    shear::matrix_entry,
+    scale::flip,
+
+    selection::select,
+    selection::deselect,
+    component_lifecycle::create,
+    component_lifecycle::delete,
+
+    translation::translation,
+    rotation::rotation,
 
+    misc::toggle_wireframes,
+    misc::increase_depth,
+    misc::decrease_depth,
+    misc::toggle_chaos,
+    misc::toggle_adaptive,
+    misc::toggle_coloring,
+    misc::increase_budget,
+    misc::decrease_budget,
+
+    weight::weight,
+    shear::shear,
+    scale::scale,
+    color::color,
+    camera::zoom,
+];
+
+/// Offer `event` to each handler in turn, stopping at the first one that handles it.
+pub fn process_input(state: &mut State, event: &mut WindowEvent) {
+    for handler in HANDLERS {
+        if let InputResult::Handled = handler(state, event) {
+            return;
+        }
+    }
 }
 
 enum InputResult {
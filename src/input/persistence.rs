@@ -0,0 +1,38 @@
+use crate::{
+    SCENE_PATH,
+    state::State,
+};
+use glfw::{Action, Key, Modifiers, WindowEvent};
+use super::InputResult;
+
+pub fn save(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::S, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if let Err(e) = state.save_scene(SCENE_PATH) {
+                eprintln!("failed to save scene to {}: {}", SCENE_PATH, e);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+pub fn load(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::O, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if let Err(e) = state.load_scene(SCENE_PATH) {
+                eprintln!("failed to load scene from {}: {}", SCENE_PATH, e);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     ROTATE_ADJUST_BASE,
     ROTATE_ADJUST_FINE,
-    state::State,
+    state::{Edit, State},
 };
 use glfw::{Action, Key, Modifiers, WindowEvent};
 use std::collections::HashSet;
@@ -16,8 +16,12 @@ static ROTATION_KEYS: HashSet<Key> = {
 pub fn rotation(state: &mut State, event: &mut WindowEvent) -> InputResult {
     use glfw::Key;
 
+    if state.selected().is_empty() {
+        return InputResult::Continue
+    }
+
     match event {
-        Key(variant, _, Action::Press, mods) if ROTATION_KEYS.contains(variant) => {
+        Key(variant, _, Action::Press, mods) if ROTATION_KEYS.contains(variant) && (*mods & Modifiers::Control).is_empty() => {
             let axis = match variant {
                 Key::I => Vector3::z(),
                 Key::K => -Vector3::z(),
@@ -27,24 +31,29 @@ pub fn rotation(state: &mut State, event: &mut WindowEvent) -> InputResult {
                 Key::O => -Vector3::y(),
             };
 
-            state.selection().map(|comp| {
-                let mut comp = comp.borrow_mut();
-                let rotate_factor = if (mods & Modifiers::Shift).is_empty() {
-                    ROTATE_ADJUST_BASE
-                } else {
-                    ROTATE_ADJUST_BASE * ROTATE_ADJUST_FINE
-                };
+            state.begin_edit(Edit::Adjust("rotate"));
+
+            let rotate_factor = if (*mods & Modifiers::Shift).is_empty() {
+                ROTATE_ADJUST_BASE
+            } else {
+                ROTATE_ADJUST_BASE * ROTATE_ADJUST_FINE
+            };
 
-                comp.orientation *= UnitQuaternion::from_axis_angle(&axis, rotate_factor);
-            });
+            state.rotate_selected(&UnitQuaternion::from_axis_angle(&axis, rotate_factor));
 
             InputResult::Handled
         },
 
-        Key(Key::Backspace, _, Action::Press, mods) if (mods & Modifiers::Shift).is_empty() => {
-            state.selection().map(|comp| {
-                comp.borrow_mut().orientation = UnitQuaternion::identity();
-            });
+        Key(Key::Backspace, _, Action::Press, mods) if (*mods & Modifiers::Shift).is_empty() => {
+            let selected = state.selected();
+
+            if selected.iter().any(|comp| comp.borrow().orientation != UnitQuaternion::identity()) {
+                state.begin_edit(Edit::Discrete);
+
+                selected.iter().for_each(|comp| {
+                    comp.borrow_mut().orientation = UnitQuaternion::identity();
+                });
+            }
 
             InputResult::Handled
         },
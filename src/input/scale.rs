@@ -1,7 +1,7 @@
 use crate::{
     SCALE_ADJUST_BASE,
     SCALE_ADJUST_FINE,
-    state::State,
+    state::{Edit, State},
 };
 use glfw::{Action, Key, Modifiers, WindowEvent};
 use std::collections::HashSet;
@@ -13,7 +13,7 @@ static SCALE_KEYS: HashSet<Key> = {
     vec! {X, Y, Z, B}.into();
 };
 
-pub fn rotation(state: &mut State, event: &mut WindowEvent) -> InputResult {
+pub fn scale(state: &mut State, event: &mut WindowEvent) -> InputResult {
     use glfw::Key;
     use glfw::WindowEvent::Scroll;
 
@@ -28,10 +28,9 @@ pub fn rotation(state: &mut State, event: &mut WindowEvent) -> InputResult {
         Scroll(unused, offset) if keys_depressed => {
             event.inhibited = true;
 
-            let comp = state.selection().unwrap();
+            state.begin_edit(Edit::Adjust("scale"));
 
             let offset = offset as f32;
-            let mut comp = comp.borrow_mut();
 
             let adjustment = if fine {
                 SCALE_ADJUST_BASE * SCALE_ADJUST_FINE
@@ -39,19 +38,19 @@ pub fn rotation(state: &mut State, event: &mut WindowEvent) -> InputResult {
                 SCALE_ADJUST_BASE
             };
 
-            if glfw_window.get_key(Key::B) == Action::Press {
-                comp.scale[0] = 0.0f32.max(comp.scale[0] + adjustment * offset);
-                comp.scale[1] = 0.0f32.max(comp.scale[1] + adjustment * offset);
-                comp.scale[2] = 0.0f32.max(comp.scale[2] + adjustment * offset);
+            let axis = if glfw_window.get_key(Key::B) == Action::Press {
+                None
             } else if glfw_window.get_key(Key::X) == Action::Press {
-                comp.scale[0] = 0.0f32.max(comp.scale[0] + adjustment * offset);
+                Some(0)
             } else if glfw_window.get_key(Key::Y) == Action::Press {
-                comp.scale[1] = 0.0f32.max(comp.scale[1] + adjustment * offset);
+                Some(1)
             } else if glfw_window.get_key(Key::Z) == Action::Press {
-                comp.scale[2] = 0.0f32.max(comp.scale[2] + adjustment * offset);
+                Some(2)
             } else {
                 return InputResult::Continue
-            }
+            };
+
+            state.scale_selected(axis, adjustment * offset);
 
             InputResult::Handled
         },
@@ -61,3 +60,34 @@ pub fn rotation(state: &mut State, event: &mut WindowEvent) -> InputResult {
         }
     }
 }
+
+
+pub fn flip(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    if state.selected().is_empty() {
+        return InputResult::Continue
+    }
+
+    match event {
+        Key(variant, _, Action::Press, mods) if !(*mods & Modifiers::Alt).is_empty() => {
+            let axis = match variant {
+                Key::X => 0,
+                Key::Y => 1,
+                Key::Z => 2,
+                _ => return InputResult::Continue,
+            };
+
+            state.begin_edit(Edit::Discrete);
+
+            state.selected().iter().for_each(|comp| {
+                let mut comp = comp.borrow_mut();
+                comp.scale[axis] = -comp.scale[axis];
+            });
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
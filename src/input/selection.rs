@@ -3,19 +3,33 @@ use crate::{
     state::world::DragState,
 };
 use glfw::{Action, Key, Modifiers, WindowEvent};
+use na::Point2;
 use std::collections::HashSet;
 use super::InputResult;
 
+/// Click a box to select it, or `Shift`-click to add it to (or remove it from) the selection.
+/// Clicking a box that's already part of a group keeps the group, so it can be dragged as a whole.
+/// Pressing on empty space starts a selection rectangle.
 pub fn select(state: &mut State, event: &mut WindowEvent) -> InputResult {
     use glfw::MouseButton;
     use glfw::WindowEvent::MouseButton;
 
     match event {
-        MouseButton(MouseButtonLeft, Action::Press, _) => {
+        MouseButton(MouseButtonLeft, Action::Press, mods) => {
+            let mouse_projection = state.project_mouse();
+
             match state.nearest_intersection() {
+                Some((ref comp, _)) if !(*mods & Modifiers::Shift).is_empty() => {
+                    comp.upgrade().into_iter().for_each(|comp| state.toggle_selected(comp));
+                },
+
                 Some((ref comp, ref intersect)) => {
                     comp.upgrade().iter().for_each(|comp| {
-                        state.select(comp.clone());
+                        if state.is_selected(&comp.borrow()) {
+                            state.make_primary(comp.clone());
+                        } else {
+                            state.select(comp.clone());
+                        }
 
                         let comp = comp.borrow();
 
@@ -23,13 +37,28 @@ pub fn select(state: &mut State, event: &mut WindowEvent) -> InputResult {
                             origin_orientation: comp.orientation,
                             local_handle_offset: intersect.coords - comp.origin,
                             camera_dist: (mouse_projection.origin.coords - intersect.coords).norm(),
+                            moved: false,
                         };
 
                         state.drag(drag_state);
                     });
                 },
 
-                None => state.deselect(),
+                None => {
+                    let (x, y) = state.glfw_window().get_cursor_pos();
+                    state.begin_box_select(Point2::new(x as f32, y as f32));
+                },
+            }
+
+            InputResult::Handled
+        },
+
+        MouseButton(MouseButtonLeft, Action::Release, mods) => {
+            state.stop_dragging();
+
+            if state.is_box_selecting() {
+                let (x, y) = state.glfw_window().get_cursor_pos();
+                state.end_box_select(Point2::new(x as f32, y as f32), !(*mods & Modifiers::Shift).is_empty());
             }
 
             InputResult::Handled
@@ -46,11 +75,12 @@ pub fn deselect(state: &mut State, event: &mut WindowEvent) -> InputResult {
     use glfw::WindowEvent::Key;
 
     match event {
-        Key(Key::Backspace, _, Action::Press, _) if (mods & Modifiers::Shift).is_empty() => {
+        Key(Key::Backspace, _, Action::Press, mods) if !(*mods & Modifiers::Shift).is_empty() => {
             state.delete_selected();
 
             InputResult::Handled
         },
 
+        _ => InputResult::Continue,
     }
 }
\ No newline at end of file
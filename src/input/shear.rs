@@ -0,0 +1,124 @@
+use crate::{
+    SHEAR_ADJUST_BASE,
+    SHEAR_ADJUST_FINE,
+    state::{Edit, State},
+};
+use glfw::{Action, Key, Modifiers, WindowEvent};
+use na::Matrix4;
+use super::InputResult;
+
+/// Keys which, held while scrolling, adjust the xy, xz and yz shear respectively.
+static SHEAR_KEYS: [Key; 3] = [Key::Num1, Key::Num2, Key::Num3];
+
+pub fn shear(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Scroll;
+
+    if state.selection().is_none() {
+        return InputResult::Continue
+    }
+
+    let (entry, fine) = {
+        let glfw_window = state.glfw_window();
+
+        let entry = SHEAR_KEYS.iter().position(|&k| glfw_window.get_key(k) == Action::Press);
+        let fine = glfw_window.get_key(Key::LeftShift) == Action::Press || glfw_window.get_key(Key::RightShift) == Action::Press;
+
+        (entry, fine)
+    };
+
+    let entry = match entry {
+        Some(entry) => entry,
+        None => return InputResult::Continue,
+    };
+
+    match event {
+        Scroll(_, offset) => {
+            let offset = *offset as f32;
+
+            event.inhibited = true;
+
+            let adjustment = if fine {
+                SHEAR_ADJUST_BASE * SHEAR_ADJUST_FINE
+            } else {
+                SHEAR_ADJUST_BASE
+            };
+
+            state.begin_edit(Edit::Adjust(["shear xy", "shear xz", "shear yz"][entry]));
+
+            state.selection().map(|comp| {
+                comp.borrow_mut().shear[entry] += adjustment * offset;
+            });
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+/// `Ctrl-M` replaces the selected transform with a 3×4 matrix read from the clipboard (twelve
+/// numbers, row by row); `Ctrl-Shift-M` copies the selected transform to the clipboard in the same
+/// form.
+pub fn matrix_entry(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    let comp = match state.selection() {
+        Some(comp) => comp,
+        None => return InputResult::Continue,
+    };
+
+    match event {
+        Key(Key::M, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            if (*mods & Modifiers::Shift).is_empty() {
+                let contents = state.clipboard();
+
+                match parse_affine(&contents) {
+                    Some(affine) => {
+                        let mut updated = comp.borrow().clone();
+
+                        if updated.set_affine(&affine) {
+                            state.begin_edit(Edit::Discrete);
+                            *comp.borrow_mut() = updated;
+                        } else {
+                            eprintln!("refusing to set a singular transform");
+                        }
+                    },
+                    None => eprintln!("clipboard doesn't hold a 3x4 matrix: {:?}", contents),
+                }
+            } else {
+                let matrix = comp.borrow().transform().to_homogeneous();
+
+                let text = (0..3)
+                    .map(|i| (0..4).map(|j| matrix[(i, j)].to_string()).collect::<Vec<_>>().join(" "))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                state.set_clipboard(&text);
+            }
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+fn parse_affine(text: &str) -> Option<Matrix4<f32>> {
+    let entries = text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if entries.len() != 12 {
+        return None;
+    }
+
+    let mut affine = Matrix4::identity();
+    for i in 0..3 {
+        for j in 0..4 {
+            affine[(i, j)] = entries[4 * i + j];
+        }
+    }
+
+    Some(affine)
+}
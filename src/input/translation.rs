@@ -1,5 +1,5 @@
 use crate::{
-    state::State,
+    state::{Edit, State},
     TRANSLATE_ADJUST_BASE,
     TRANSLATE_ADJUST_FINE,
 };
@@ -16,8 +16,12 @@ static TRANSLATION_KEYS: HashSet<Key> = {
 pub fn translation(state: &mut State, event: &mut WindowEvent) -> InputResult {
     use glfw::Key;
 
+    if state.selected().is_empty() {
+        return InputResult::Continue
+    }
+
     match event {
-        Key(variant, _, Action::Press, mods) if TRANSLATION_KEYS.contains(variant) && !state.is_dragging() => {
+        Key(variant, _, Action::Press, mods) if TRANSLATION_KEYS.contains(variant) && (*mods & Modifiers::Control).is_empty() && !state.is_dragging() => {
             let adjust = match variant {
                 Key::W => Vector3::z(),
                 Key::S => -Vector3::z(),
@@ -27,16 +31,15 @@ pub fn translation(state: &mut State, event: &mut WindowEvent) -> InputResult {
                 Key::F => -Vector3::y(),
             };
 
-            state.selection().map(|comp| {
-                let mut comp = comp.borrow_mut();
-                let translate_factor = if (mods & Modifiers::Shift).is_empty() {
-                    TRANSLATE_ADJUST_BASE
-                } else {
-                    TRANSLATE_ADJUST_BASE * TRANSLATE_ADJUST_FINE
-                };
+            state.begin_edit(Edit::Adjust("translate"));
+
+            let translate_factor = if (*mods & Modifiers::Shift).is_empty() {
+                TRANSLATE_ADJUST_BASE
+            } else {
+                TRANSLATE_ADJUST_BASE * TRANSLATE_ADJUST_FINE
+            };
 
-                comp.origin += translate_factor * adjust;
-            });
+            state.translate_selected(&(translate_factor * adjust));
 
             InputResult::Handled
         },
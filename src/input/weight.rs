@@ -0,0 +1,58 @@
+use crate::{
+    WEIGHT_ADJUST_BASE,
+    WEIGHT_ADJUST_FINE,
+    state::State,
+};
+use glfw::{Action, Key, Modifiers, WindowEvent};
+use super::InputResult;
+
+/// Hold `Q` and scroll to adjust the selected component's probability.
+pub fn weight(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Scroll;
+
+    if state.selection().is_none() {
+        return InputResult::Continue
+    }
+
+    let (held, fine) = {
+        let glfw_window = state.glfw_window();
+
+        let held = glfw_window.get_key(Key::Q) == Action::Press;
+        let fine = glfw_window.get_key(Key::LeftShift) == Action::Press || glfw_window.get_key(Key::RightShift) == Action::Press;
+
+        (held, fine)
+    };
+
+    match event {
+        Scroll(_, offset) if held => {
+            let offset = *offset as f32;
+
+            event.inhibited = true;
+
+            let adjustment = if fine {
+                WEIGHT_ADJUST_BASE * WEIGHT_ADJUST_FINE
+            } else {
+                WEIGHT_ADJUST_BASE
+            };
+
+            state.adjust_weight(adjustment * offset);
+
+            InputResult::Handled
+        },
+
+        _ => InputResult::Continue,
+    }
+}
+
+/// `Ctrl-Q` returns every component to automatic, volume-proportional weights.
+pub fn reset_weights(state: &mut State, event: &mut WindowEvent) -> InputResult {
+    use glfw::WindowEvent::Key;
+
+    match event {
+        Key(Key::Q, _, Action::Press, mods) if !(*mods & Modifiers::Control).is_empty() => {
+            state.reset_weights();
+            InputResult::Handled
+        },
+        _ => InputResult::Continue,
+    }
+}
@@ -0,0 +1,105 @@
+//! Batching of leaf cubes into a handful of large meshes.
+//!
+//! kiss3d draws each mesh with a single color, so leaves are bucketed by (quantized) color, and
+//! each bucket is split into chunks small enough for 16-bit vertex indices.
+//!
+//! Every leaf is emitted as the exact parallelepiped its full affine transform maps the unit cube
+//! to, so shear and reflections in composite maps come out as they should.
+
+use crate::{
+    geometry::{self, UNIT_CUBE_FACES},
+    ifs::Leaf,
+};
+use na::{
+    Matrix4,
+    Point3,
+    Vector3,
+};
+use std::collections::BTreeMap;
+
+/// Number of levels each color channel is quantized to when bucketing leaves.
+pub const COLOR_LEVELS: f32 = 64.0;
+
+/// Flat shading needs a separate copy of each corner per face.
+const VERTICES_PER_CUBE: usize = 4 * 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshChunk {
+    pub color: Vector3<f32>,
+    pub coords: Vec<Point3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub faces: Vec<Point3<u16>>,
+}
+
+impl MeshChunk {
+    fn new(color: Vector3<f32>) -> Self {
+        MeshChunk {
+            color,
+            coords: Vec::new(),
+            normals: Vec::new(),
+            faces: Vec::new(),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.coords.len() + VERTICES_PER_CUBE > u16::max_value() as usize + 1
+    }
+
+    /// A single cube transformed by `transform`.
+    pub fn cube(transform: &Matrix4<f32>, color: Vector3<f32>) -> Self {
+        let mut chunk = MeshChunk::new(color);
+        chunk.push_cube(transform);
+
+        chunk
+    }
+
+    fn push_cube(&mut self, transform: &Matrix4<f32>) {
+        let (corners, reflected) = geometry::cube_corners(transform);
+
+        for face in UNIT_CUBE_FACES.iter() {
+            let mut face = *face;
+            if reflected {
+                face.reverse();
+            }
+
+            let base = self.coords.len() as u16;
+            let normal = (corners[face[1]] - corners[face[0]]).cross(&(corners[face[3]] - corners[face[0]]))
+                .try_normalize(1.0e-12)
+                .unwrap_or_else(Vector3::zeros);
+
+            for &vertex in face.iter() {
+                self.coords.push(corners[vertex]);
+                self.normals.push(normal);
+            }
+
+            self.faces.push(Point3::new(base, base + 1, base + 2));
+            self.faces.push(Point3::new(base, base + 2, base + 3));
+        }
+    }
+}
+
+/// Build meshes covering every leaf. Chunks come out in a fixed order for a given input.
+pub fn build_chunks(leaves: &[Leaf]) -> Vec<MeshChunk> {
+    let mut buckets: BTreeMap<(u8, u8, u8), Vec<MeshChunk>> = BTreeMap::new();
+
+    for leaf in leaves.iter() {
+        let key = (quantize(leaf.color[0]), quantize(leaf.color[1]), quantize(leaf.color[2]));
+
+        let chunks = buckets.entry(key).or_insert_with(Vec::new);
+
+        if chunks.last().map_or(true, |chunk| chunk.is_full()) {
+            let color = Vector3::new(key.0 as f32, key.1 as f32, key.2 as f32) / (COLOR_LEVELS - 1.0);
+            chunks.push(MeshChunk::new(color));
+        }
+
+        chunks.last_mut().unwrap().push_cube(&leaf.transform);
+    }
+
+    buckets.into_iter()
+        .flat_map(|(_, chunks)| chunks)
+        .collect()
+}
+
+fn quantize(channel: f32) -> u8 {
+    (channel.max(0.0).min(1.0) * (COLOR_LEVELS - 1.0)).round() as u8
+}
@@ -0,0 +1,134 @@
+//! Window-independent camera projections.
+
+use na::{
+    Isometry3,
+    Matrix4,
+    Perspective3,
+    Point2,
+    Point3,
+    Vector3,
+    Vector4,
+};
+use crate::geometry;
+use nc::query::Ray3;
+
+/// Field of view, in radians, of the viewer's default camera.
+pub const DEFAULT_FOVY: f32 = std::f32::consts::FRAC_PI_4;
+
+const ZNEAR: f32 = 0.1;
+const ZFAR: f32 = 1024.0;
+
+/// A perspective camera described without reference to any window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+
+    /// Vertical field of view, in radians.
+    pub fovy: f32,
+}
+
+impl Default for View {
+    /// Where the viewer's camera starts.
+    fn default() -> Self {
+        View {
+            eye: Point3::new(0.0, 0.0, -4.0),
+            target: Point3::origin(),
+            fovy: DEFAULT_FOVY,
+        }
+    }
+}
+
+impl View {
+    pub fn viewport(&self, width: usize, height: usize) -> Viewport {
+        let projection = Perspective3::new(width as f32 / height as f32, self.fovy, ZNEAR, ZFAR);
+        let view = Isometry3::look_at_rh(&self.eye, &self.target, &Vector3::y());
+
+        Viewport::new(projection.as_matrix() * view.to_homogeneous(), width as f32, height as f32)
+    }
+}
+
+/// Maps world space onto a `width` × `height` pixel image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// World-to-clip-space transform, i.e. projection × view.
+    pub transform: Matrix4<f32>,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn new(transform: Matrix4<f32>, width: f32, height: f32) -> Self {
+        Viewport {
+            transform,
+            width,
+            height,
+        }
+    }
+
+    /// Pixel coordinates of `point` (origin at the top left), or `None` if it's behind the camera.
+    pub fn project(&self, point: &Point3<f32>) -> Option<Point2<f32>> {
+        self.project_with_depth(point).map(|p| Point2::new(p[0], p[1]))
+    }
+
+    /// Like `project`, with normalized device depth (`-1` at the near plane, `1` at the far
+    /// plane) as the third coordinate.
+    pub fn project_with_depth(&self, point: &Point3<f32>) -> Option<Point3<f32>> {
+        let clip = self.transform * Vector4::new(point[0], point[1], point[2], 1.0);
+
+        if clip[3] <= 0.0 {
+            return None;
+        }
+
+        let ndc_x = clip[0] / clip[3];
+        let ndc_y = clip[1] / clip[3];
+        let ndc_z = clip[2] / clip[3];
+
+        Some(Point3::new((ndc_x + 1.0) / 2.0 * self.width, (1.0 - ndc_y) / 2.0 * self.height, ndc_z))
+    }
+
+    /// Ray from the near plane through the pixel coordinates `pixel`, with a unit direction.
+    pub fn ray(&self, pixel: &Point2<f32>) -> Ray3<f32> {
+        let inverse = self.transform.try_inverse().unwrap_or_else(Matrix4::identity);
+
+        let ndc_x = pixel[0] / self.width * 2.0 - 1.0;
+        let ndc_y = 1.0 - pixel[1] / self.height * 2.0;
+
+        let unproject = |ndc_z: f32| {
+            let world = inverse * Vector4::new(ndc_x, ndc_y, ndc_z, 1.0);
+            Point3::new(world[0], world[1], world[2]) / world[3]
+        };
+
+        let (near, far) = (unproject(-1.0), unproject(1.0));
+
+        Ray3::new(near, (far - near).normalize())
+    }
+
+    /// Size in pixels of the unit cube under `transform`, taken as the longer side of its
+    /// on-screen bounding box. Infinite if the cube straddles the camera, zero if it's entirely
+    /// behind it.
+    pub fn projected_size(&self, transform: &Matrix4<f32>) -> f32 {
+        use std::f32::{INFINITY, NEG_INFINITY};
+
+        let projected = geometry::cube_corners(transform).0.iter()
+            .map(|corner| self.project(corner))
+            .collect::<Vec<_>>();
+
+        if projected.iter().all(|p| p.is_none()) {
+            return 0.0;
+        }
+
+        if projected.iter().any(|p| p.is_none()) {
+            return INFINITY;
+        }
+
+        let (min, max) = projected.into_iter()
+            .filter_map(|p| p)
+            .fold(((INFINITY, INFINITY), (NEG_INFINITY, NEG_INFINITY)), |((min_x, min_y), (max_x, max_y)), p| (
+                (min_x.min(p[0]), min_y.min(p[1])),
+                (max_x.max(p[0]), max_y.max(p[1])),
+            ));
+
+        (max.0 - min.0).max(max.1 - min.1)
+    }
+}
@@ -0,0 +1,194 @@
+//! Flame-style density rendering: chaos game points are binned per pixel and the bin counts are
+//! tone mapped on a log scale, so both the sparse outskirts and the dense core of the attractor
+//! stay visible.
+
+use crate::{
+    ifs::System,
+    projection::Viewport,
+};
+use na::{Point3, Vector3};
+use super::Image;
+
+/// Number of independent chaos game walks a render is split into. Fixed, so the output doesn't
+/// depend on how many threads happen to be available.
+const STREAMS: usize = 64;
+
+/// Number of histograms the streams are accumulated into in parallel, each holding an equal run of
+/// consecutive streams. This bounds how many full-size histograms are alive at once.
+const GROUPS: usize = 8;
+
+/// Controls how bin counts become pixel values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    /// Multiplier on the log-density of every pixel.
+    pub brightness: f32,
+
+    pub gamma: f32,
+
+    /// How much gamma is applied to the density alone rather than to each color channel. At `1`,
+    /// colors keep their saturation in dim areas; at `0`, they wash out towards black.
+    pub vibrancy: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            brightness: 1.0,
+            gamma: 2.2,
+            vibrancy: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Bin {
+    count: f32,
+
+    /// Sum of the colors of every point in the bin.
+    color: Vector3<f32>,
+}
+
+/// Per-pixel point counts and color sums.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    width: usize,
+    height: usize,
+    bins: Vec<Bin>,
+}
+
+impl Histogram {
+    pub fn new(width: usize, height: usize) -> Self {
+        Histogram {
+            width,
+            height,
+            bins: vec![Bin::default(); width * height],
+        }
+    }
+
+    /// Accumulate `points` of the attractor of `system` as seen through `viewport`, which should
+    /// be the size of this histogram. Deterministic for a given system, viewport and count.
+    ///
+    /// Color sums are floating point, so the order they're added in matters: each group folds its
+    /// streams in order, and the groups are merged in order afterwards, whatever the threads do.
+    pub fn render(system: &System, viewport: &Viewport, points: usize) -> Self {
+        use rayon::prelude::*;
+
+        let (width, height) = (viewport.width as usize, viewport.height as usize);
+
+        (0..GROUPS)
+            .into_par_iter()
+            .map(|group| {
+                let mut histogram = Histogram::new(width, height);
+
+                for stream in group * STREAMS / GROUPS..(group + 1) * STREAMS / GROUPS {
+                    let count = points / STREAMS + if stream < points % STREAMS { 1 } else { 0 };
+                    histogram.accumulate(viewport, system.chaos_stream(stream as u64).take(count));
+                }
+
+                histogram
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .fold(Histogram::new(width, height), |mut acc, histogram| {
+                acc.merge(&histogram);
+                acc
+            })
+    }
+
+    /// Add every point that lands inside the image. Points behind the camera or off screen are
+    /// dropped.
+    pub fn accumulate<I: IntoIterator<Item = (Point3<f32>, Vector3<f32>)>>(&mut self, viewport: &Viewport, points: I) {
+        points.into_iter().for_each(|(point, color)| {
+            let pixel = match viewport.project(&point) {
+                Some(pixel) => pixel,
+                None => return,
+            };
+
+            if pixel[0] < 0.0 || pixel[1] < 0.0 {
+                return;
+            }
+
+            let (x, y) = (pixel[0] as usize, pixel[1] as usize);
+            if x >= self.width || y >= self.height {
+                return;
+            }
+
+            let bin = &mut self.bins[y * self.width + x];
+            bin.count += 1.0;
+            bin.color += color;
+        });
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        assert_eq!((self.width, self.height), (other.width, other.height), "histogram sizes differ");
+
+        self.bins.iter_mut()
+            .zip(&other.bins)
+            .for_each(|(bin, other)| {
+                bin.count += other.count;
+                bin.color += other.color;
+            });
+    }
+
+    /// Tone map the histogram into an image on a black background.
+    pub fn to_image(&self, tone: &ToneMapping) -> Image {
+        let max_count = self.bins.iter().fold(0.0f32, |max, bin| max.max(bin.count));
+        let log_max = (1.0 + max_count).ln();
+
+        let mut image = Image::new(self.width, self.height);
+
+        if log_max <= 0.0 {
+            return image;
+        }
+
+        let inv_gamma = 1.0 / tone.gamma;
+
+        image.pixels.iter_mut()
+            .zip(&self.bins)
+            .filter(|(_, bin)| bin.count > 0.0)
+            .for_each(|(pixel, bin)| {
+                let alpha = ((1.0 + bin.count).ln() / log_max * tone.brightness).min(1.0);
+                let color = bin.color / bin.count;
+
+                let vibrant = color * alpha.powf(inv_gamma);
+                let washed = (color * alpha).map(|c| c.max(0.0).powf(inv_gamma));
+
+                *pixel = vibrant * tone.vibrancy + washed * (1.0 - tone.vibrancy);
+            });
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ifs::{Map, System},
+        projection::View,
+    };
+    use na::{Matrix4, Vector3};
+    use rayon::ThreadPoolBuilder;
+    use super::Histogram;
+
+    #[test]
+    fn render_is_independent_of_thread_count() {
+        let maps = [Vector3::new(0.0, 0.5, 0.0), Vector3::new(-0.5, -0.5, 0.0), Vector3::new(0.5, -0.5, 0.3)]
+            .iter()
+            .map(|corner| Map {
+                transform: Matrix4::new_translation(corner) * Matrix4::new_scaling(0.5),
+                color: Vector3::new(1.0, 0.5, 0.0),
+                weight: None,
+            })
+            .collect();
+
+        let system = System::new(maps);
+        let viewport = View::default().viewport(32, 24);
+
+        let single = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let serial = single.install(|| Histogram::render(&system, &viewport, 100_000));
+        let parallel = Histogram::render(&system, &viewport, 100_000);
+
+        assert!(serial.bins.iter().any(|bin| bin.count > 0.0));
+        assert_eq!(serial, parallel);
+    }
+}
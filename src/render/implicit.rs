@@ -0,0 +1,187 @@
+//! Ray casting against the expansion tree itself, without ever flattening it into leaves.
+//!
+//! Every node of the tree is bounded by the system's `BoundingBall` mapped through the node's
+//! composite transform, so a ray only descends into the children whose balls it hits, nearest
+//! first. Descent stops at the maximum depth or once a node is smaller than a pixel as seen from
+//! the eye, where the node's own cube is drawn. Since only the nodes along each ray are visited,
+//! this reaches depths whose full trees could never be stored.
+
+use alga::linear::Transformation;
+use crate::{
+    geometry,
+    ifs::{lipschitz, BoundingBall, System},
+    projection::View,
+};
+use failure::Fallible;
+use na::{Matrix4, Point3};
+use nc::query::Ray3;
+use super::trace::{Hit, Surface};
+
+/// Nodes are drawn as soon as their bounding ball is narrower than this many pixels.
+const FOOTPRINT_PIXELS: f32 = 1.0;
+
+/// A node of the expansion tree, with what's needed to test rays against it.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    transform: Matrix4<f32>,
+    inverse: Matrix4<f32>,
+
+    /// Upper bound on how much `transform` stretches distances.
+    contraction: f32,
+}
+
+/// An iterated function system as a `Surface`, expanded lazily along each ray.
+pub struct Implicit<'a> {
+    system: &'a System,
+    ball: BoundingBall,
+
+    /// Inverse of each map, or `None` for singular maps, whose subtrees are flat and never drawn.
+    inverses: Vec<Option<Matrix4<f32>>>,
+    contractions: Vec<f32>,
+
+    max_depth: usize,
+
+    /// Level of detail is chosen from the camera rather than per ray, so shadow and occlusion rays
+    /// see exactly the same geometry as camera rays.
+    eye: Point3<f32>,
+
+    /// Width of a pixel at unit distance from the eye.
+    pixel_size: f32,
+}
+
+impl<'a> Implicit<'a> {
+    /// Prepare `system` to be rendered through `view` into an image `height` pixels tall, to at
+    /// most `max_depth`. Fails if the system isn't contractive, since then there's no bound on
+    /// where its subtrees end up.
+    pub fn new(system: &'a System, view: &View, height: usize, max_depth: usize) -> Fallible<Self> {
+        let ball = system.bounding_ball()
+            .ok_or_else(|| format_err!("every map must be a contraction to ray cast the system directly"))?;
+
+        Ok(Implicit {
+            system,
+            ball,
+            inverses: system.maps().iter().map(|map| map.transform.try_inverse()).collect(),
+            contractions: system.maps().iter().map(|map| lipschitz(&map.transform)).collect(),
+            max_depth,
+            eye: view.eye,
+            pixel_size: 2.0 * (view.fovy / 2.0).tan() / height as f32,
+        })
+    }
+
+    /// Entry time of `ray` into the ball bounding `node`, if that's before `max_toi`.
+    fn toi_with_bounds(&self, node: &Node, ray: &Ray3<f32>, max_toi: f32) -> Option<f32> {
+        let center = node.transform.transform_point(&self.ball.center);
+        let radius = node.contraction * self.ball.radius;
+
+        let offset = ray.origin - center;
+        let a = ray.dir.norm_squared();
+        let b = offset.dot(&ray.dir);
+        let c = offset.norm_squared() - radius * radius;
+
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let root = discriminant.sqrt();
+        let (near, far) = ((-b - root) / a, (-b + root) / a);
+
+        if far < 0.0 || near >= max_toi {
+            return None;
+        }
+
+        Some(near.max(0.0))
+    }
+
+    /// Whether `node` is small enough on screen to draw as a single cube.
+    fn is_terminal(&self, node: &Node, depth: usize) -> bool {
+        if depth >= self.max_depth + 1 {
+            return true;
+        }
+
+        let center = node.transform.transform_point(&self.ball.center);
+        let radius = node.contraction * self.ball.radius;
+
+        2.0 * radius < FOOTPRINT_PIXELS * self.pixel_size * (center - self.eye).norm()
+    }
+
+    /// Search the subtree under `node` (at `address`) for hits nearer than `nearest`. With
+    /// `any_hit`, stop at the first one found.
+    fn visit(&self, node: &Node, ray: &Ray3<f32>, max_toi: f32, address: &mut Vec<usize>, nearest: &mut Option<Hit>, any_hit: bool) {
+        use std::cmp::Ordering;
+
+        let mut children = self.inverses.iter()
+            .enumerate()
+            .filter_map(|(i, inverse)| {
+                let child = Node {
+                    transform: node.transform * self.system.maps()[i].transform,
+                    inverse: (*inverse)? * node.inverse,
+                    contraction: node.contraction * self.contractions[i],
+                };
+
+                let limit = nearest.map_or(max_toi, |hit| hit.toi);
+                self.toi_with_bounds(&child, ray, limit).map(|toi| (toi, i, child))
+            })
+            .collect::<Vec<_>>();
+
+        // nearest first, so farther children can usually be skipped entirely
+        children.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal).then(a.1.cmp(&b.1)));
+
+        for (toi, i, child) in children {
+            if any_hit && nearest.is_some() {
+                return;
+            }
+
+            if toi >= nearest.map_or(max_toi, |hit| hit.toi) {
+                continue;
+            }
+
+            address.push(i);
+
+            if self.is_terminal(&child, address.len()) {
+                match geometry::cast_unit_cube(&child.inverse, ray, false) {
+                    Some(ref intersection) if intersection.toi < nearest.map_or(max_toi, |hit| hit.toi) => {
+                        *nearest = Some(Hit {
+                            toi: intersection.toi,
+                            normal: intersection.normal,
+                            color: self.system.blend(address.iter().cloned()),
+                        });
+                    },
+                    _ => {},
+                }
+            } else {
+                self.visit(&child, ray, max_toi, address, nearest, any_hit);
+            }
+
+            address.pop();
+        }
+    }
+
+    fn root() -> Node {
+        Node {
+            transform: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+            contraction: 1.0,
+        }
+    }
+}
+
+impl<'a> Surface for Implicit<'a> {
+    fn cast(&self, ray: &Ray3<f32>, max_toi: f32) -> Option<Hit> {
+        let mut nearest = None;
+        self.visit(&Self::root(), ray, max_toi, &mut Vec::with_capacity(self.max_depth + 1), &mut nearest, false);
+
+        nearest
+    }
+
+    fn occluded(&self, ray: &Ray3<f32>, max_toi: f32) -> bool {
+        let mut nearest = None;
+        self.visit(&Self::root(), ray, max_toi, &mut Vec::with_capacity(self.max_depth + 1), &mut nearest, true);
+
+        nearest.is_some()
+    }
+
+    fn size(&self) -> f32 {
+        2.0 * self.ball.radius
+    }
+}
@@ -0,0 +1,58 @@
+//! Offline renderers. These run entirely on the CPU and never touch a window, so they work on
+//! machines without a display.
+
+use failure::Fallible;
+use na::Vector3;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+pub mod histogram;
+pub mod implicit;
+pub mod raster;
+pub mod trace;
+
+/// An RGB image with floating point channels, in display space with `[0, 1]` as the visible
+/// range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+
+    /// Row-major from the top left.
+    pub pixels: Vec<Vector3<f32>>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![Vector3::zeros(); width * height],
+        }
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Fallible<()> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+
+        let data = self.pixels.iter()
+            .flat_map(|p| (0..3).map(move |i| (p[i].max(0.0).min(1.0) * 255.0).round() as u8))
+            .collect::<Vec<_>>();
+
+        encoder.write_header()?.write_image_data(&data)?;
+
+        Ok(())
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Fallible<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Convert a linear color to (approximately) sRGB.
+pub fn to_display(color: &Vector3<f32>) -> Vector3<f32> {
+    color.map(|c| c.max(0.0).powf(1.0 / 2.2))
+}
@@ -0,0 +1,106 @@
+//! Z-buffered software rasterization of leaf cubes, with flat shading from a light at the eye
+//! (like the viewer's default light).
+
+use crate::{
+    geometry::{self, UNIT_CUBE_FACES},
+    ifs::Leaf,
+    projection::View,
+};
+use na::{Point3, Vector3};
+use std::f32::INFINITY;
+use super::{to_display, Image};
+
+/// Fraction of each leaf's color that's lit regardless of orientation.
+const AMBIENT: f32 = 0.2;
+
+/// Render `leaves` through `view` into a `width` × `height` image on a black background.
+pub fn rasterize(leaves: &[Leaf], view: &View, width: usize, height: usize) -> Image {
+    let viewport = view.viewport(width, height);
+
+    let mut image = Image::new(width, height);
+    let mut depth = vec![INFINITY; width * height];
+
+    leaves.iter().for_each(|leaf| {
+        let (corners, _) = geometry::cube_corners(&leaf.transform);
+
+        UNIT_CUBE_FACES.iter().for_each(|face| {
+            let world = [corners[face[0]], corners[face[1]], corners[face[2]], corners[face[3]]];
+
+            // whichever way the leaf's transform winds this face, the side facing the eye is lit
+            let mut normal = match (world[1] - world[0]).cross(&(world[3] - world[0])).try_normalize(1.0e-12) {
+                Some(normal) => normal,
+                None => return,
+            };
+
+            let center = Point3::from_coordinates((world[0].coords + world[1].coords + world[2].coords + world[3].coords) / 4.0);
+            let to_eye = match (view.eye - center).try_normalize(1.0e-12) {
+                Some(to_eye) => to_eye,
+                None => return,
+            };
+
+            if normal.dot(&to_eye) < 0.0 {
+                normal = -normal;
+            }
+
+            let shade = AMBIENT + (1.0 - AMBIENT) * normal.dot(&to_eye);
+            let color = to_display(&(leaf.color * shade));
+
+            // faces that cross behind the camera are dropped rather than clipped
+            let screen = match (viewport.project_with_depth(&world[0]), viewport.project_with_depth(&world[1]),
+                                viewport.project_with_depth(&world[2]), viewport.project_with_depth(&world[3])) {
+                (Some(a), Some(b), Some(c), Some(d)) => [a, b, c, d],
+                _ => return,
+            };
+
+            for triangle in &[[0, 1, 2], [0, 2, 3]] {
+                fill(&mut image, &mut depth, [screen[triangle[0]], screen[triangle[1]], screen[triangle[2]]], color);
+            }
+        });
+    });
+
+    image
+}
+
+/// Fill the pixels whose centers lie inside `triangle` and in front of `depth`.
+fn fill(image: &mut Image, depth: &mut [f32], triangle: [Point3<f32>; 3], color: Vector3<f32>) {
+    let [a, b, c] = triangle;
+
+    let area = edge(&a, &b, &c);
+    if area.abs() < 1.0e-12 {
+        return;
+    }
+
+    let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize;
+    let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize;
+    let max_x = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as usize).min(image.width);
+    let max_y = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as usize).min(image.height);
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let p = Point3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+
+            let w0 = edge(&b, &c, &p) / area;
+            let w1 = edge(&c, &a, &p) / area;
+            let w2 = edge(&a, &b, &p) / area;
+
+            if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                continue;
+            }
+
+            let z = w0 * a[2] + w1 * b[2] + w2 * c[2];
+            let idx = y * image.width + x;
+
+            if z < -1.0 || z >= depth[idx] {
+                continue;
+            }
+
+            depth[idx] = z;
+            image.pixels[idx] = color;
+        }
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p` in screen space.
+fn edge(a: &Point3<f32>, b: &Point3<f32>, p: &Point3<f32>) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
@@ -0,0 +1,447 @@
+//! Offline ray tracing of leaf cubes, with hard shadows from a directional light and ambient
+//! occlusion.
+//!
+//! Anything that can answer ray queries is a `Surface`. For flattened leaves, that's a bounding
+//! volume hierarchy of world-space boxes; at the bottom, rays are cast against each leaf's exact
+//! parallelepiped with `geometry::cast_unit_cube`, the same test picking uses. Every random sample
+//! is drawn from a generator seeded by its pixel, so a render depends only on its inputs, never on
+//! thread scheduling.
+
+use crate::{
+    geometry,
+    ifs::Leaf,
+    projection::View,
+};
+use na::{Matrix4, Point2, Point3, Vector3};
+use nc::query::Ray3;
+use rand::{
+    prng::XorShiftRng,
+    Rng,
+    SeedableRng,
+};
+use std::f32::{INFINITY, NEG_INFINITY};
+use super::{to_display, Image};
+
+/// Leaves per BVH node below which it isn't split any further.
+const MAX_NODE_LEAVES: usize = 4;
+
+/// How far secondary rays start from the surface they leave, relative to the scene's size, so they
+/// don't hit it again.
+const SURFACE_OFFSET: f32 = 1.0e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceSettings {
+    /// Direction towards the light.
+    pub light: Vector3<f32>,
+
+    /// Fraction of light that reaches every surface regardless of the light, before occlusion.
+    pub ambient: f32,
+
+    /// Number of ambient occlusion rays cast from each hit (so per camera ray, not per pixel). `0`
+    /// disables ambient occlusion.
+    pub ao_samples: usize,
+
+    /// Distance, relative to the scene's size, within which geometry occludes ambient light.
+    pub ao_distance: f32,
+
+    /// Number of jittered camera rays per pixel.
+    pub samples: usize,
+
+    pub background: Vector3<f32>,
+}
+
+impl Default for TraceSettings {
+    fn default() -> Self {
+        TraceSettings {
+            light: Vector3::new(0.4, 0.8, -0.5).normalize(),
+            ambient: 0.3,
+            ao_samples: 16,
+            ao_distance: 0.1,
+            samples: 4,
+            background: Vector3::new(0.05, 0.05, 0.05),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Aabb {
+    min: Point3<f32>,
+    max: Point3<f32>,
+}
+
+impl Aabb {
+    fn empty() -> Self {
+        Aabb {
+            min: Point3::new(INFINITY, INFINITY, INFINITY),
+            max: Point3::new(NEG_INFINITY, NEG_INFINITY, NEG_INFINITY),
+        }
+    }
+
+    fn grow(&self, point: &Point3<f32>) -> Self {
+        Aabb {
+            min: Point3::new(self.min[0].min(point[0]), self.min[1].min(point[1]), self.min[2].min(point[2])),
+            max: Point3::new(self.max[0].max(point[0]), self.max[1].max(point[1]), self.max[2].max(point[2])),
+        }
+    }
+
+    fn merge(&self, other: &Aabb) -> Self {
+        self.grow(&other.min).grow(&other.max)
+    }
+
+    fn center(&self) -> Point3<f32> {
+        Point3::from_coordinates((self.min.coords + self.max.coords) / 2.0)
+    }
+
+    /// Entry time of `ray` into this box if it enters before `max_toi`, by the slab method.
+    fn toi_with_ray(&self, ray: &Ray3<f32>, max_toi: f32) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, max_toi);
+
+        for i in 0..3 {
+            let inv = 1.0 / ray.dir[i];
+            let t0 = (self.min[i] - ray.origin[i]) * inv;
+            let t1 = (self.max[i] - ray.origin[i]) * inv;
+
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+
+            // NaN (a ray in the plane of a slab) fails this and counts as a miss
+            if !(near <= far) {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Primitive {
+    /// Inverse of the leaf's transform, carrying rays into its local frame.
+    inverse: Matrix4<f32>,
+    bounds: Aabb,
+    color: Vector3<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Node {
+    /// Primitives `start..end`.
+    Leaf { bounds: Aabb, start: usize, end: usize },
+
+    /// The right child's index; the left child immediately follows its parent.
+    Branch { bounds: Aabb, right: usize },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Branch { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// A surface hit by a ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub toi: f32,
+    pub normal: Vector3<f32>,
+    pub color: Vector3<f32>,
+}
+
+/// Geometry a ray tracer can render.
+pub trait Surface {
+    /// Nearest surface `ray` hits before `max_toi`.
+    fn cast(&self, ray: &Ray3<f32>, max_toi: f32) -> Option<Hit>;
+
+    /// Whether anything blocks `ray` before `max_toi`.
+    fn occluded(&self, ray: &Ray3<f32>, max_toi: f32) -> bool {
+        self.cast(ray, max_toi).is_some()
+    }
+
+    /// Rough diameter of the whole surface, which secondary ray distances are relative to.
+    fn size(&self) -> f32;
+}
+
+/// Bounding volume hierarchy over the leaves of an expansion.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    primitives: Vec<Primitive>,
+    nodes: Vec<Node>,
+}
+
+impl Bvh {
+    /// Build a hierarchy over `leaves`, splitting at the median along the longest axis of the
+    /// leaves' centers. Degenerate (singular) leaves are skipped.
+    pub fn new(leaves: &[Leaf]) -> Self {
+        let mut primitives = leaves.iter()
+            .filter_map(|leaf| {
+                let inverse = leaf.transform.try_inverse()?;
+                let bounds = geometry::cube_corners(&leaf.transform).0.iter()
+                    .fold(Aabb::empty(), |bounds, corner| bounds.grow(corner));
+
+                Some(Primitive {
+                    inverse,
+                    bounds,
+                    color: leaf.color,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut nodes = Vec::new();
+        if !primitives.is_empty() {
+            let len = primitives.len();
+            build(&mut primitives, 0, len, &mut nodes);
+        }
+
+        Bvh {
+            primitives,
+            nodes,
+        }
+    }
+}
+
+impl Surface for Bvh {
+    /// Length of the diagonal of the whole scene's bounding box.
+    fn size(&self) -> f32 {
+        self.nodes.first().map_or(0.0, |node| (node.bounds().max - node.bounds().min).norm())
+    }
+
+    fn cast(&self, ray: &Ray3<f32>, max_toi: f32) -> Option<Hit> {
+        let mut nearest: Option<Hit> = None;
+        let mut stack = Vec::with_capacity(64);
+
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(idx) = stack.pop() {
+            let limit = nearest.map_or(max_toi, |hit| hit.toi);
+
+            let node = &self.nodes[idx];
+            if node.bounds().toi_with_ray(ray, limit).is_none() {
+                continue;
+            }
+
+            match *node {
+                Node::Leaf { start, end, .. } => {
+                    self.primitives[start..end].iter().for_each(|primitive| {
+                        let limit = nearest.map_or(max_toi, |hit| hit.toi);
+
+                        match geometry::cast_unit_cube(&primitive.inverse, ray, false) {
+                            Some(ref intersection) if intersection.toi < limit => {
+                                nearest = Some(Hit {
+                                    toi: intersection.toi,
+                                    normal: intersection.normal,
+                                    color: primitive.color,
+                                });
+                            },
+                            _ => {},
+                        }
+                    });
+                },
+
+                Node::Branch { right, .. } => {
+                    stack.push(right);
+                    stack.push(idx + 1);
+                },
+            }
+        }
+
+        nearest
+    }
+
+    fn occluded(&self, ray: &Ray3<f32>, max_toi: f32) -> bool {
+        let mut stack = vec![];
+
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            if node.bounds().toi_with_ray(ray, max_toi).is_none() {
+                continue;
+            }
+
+            match *node {
+                Node::Leaf { start, end, .. } => {
+                    let blocked = self.primitives[start..end].iter()
+                        .any(|primitive| geometry::cast_unit_cube(&primitive.inverse, ray, false).map_or(false, |i| i.toi < max_toi));
+
+                    if blocked {
+                        return true;
+                    }
+                },
+
+                Node::Branch { right, .. } => {
+                    stack.push(right);
+                    stack.push(idx + 1);
+                },
+            }
+        }
+
+        false
+    }
+}
+
+/// Append the subtree over `primitives[start..end]` to `nodes`, reordering those primitives.
+fn build(primitives: &mut [Primitive], start: usize, end: usize, nodes: &mut Vec<Node>) {
+    use std::cmp::Ordering;
+
+    let bounds = primitives[start..end].iter().fold(Aabb::empty(), |bounds, p| bounds.merge(&p.bounds));
+
+    if end - start <= MAX_NODE_LEAVES {
+        nodes.push(Node::Leaf { bounds, start, end });
+        return;
+    }
+
+    let centers = primitives[start..end].iter().fold(Aabb::empty(), |centers, p| centers.grow(&p.bounds.center()));
+    let extent = centers.max - centers.min;
+    let axis = (0..3).fold(0, |best, i| if extent[i] > extent[best] { i } else { best });
+
+    // ties fall back to the original order, so the tree doesn't depend on the sort algorithm
+    primitives[start..end].sort_by(|a, b| {
+        a.bounds.center()[axis].partial_cmp(&b.bounds.center()[axis]).unwrap_or(Ordering::Equal)
+    });
+
+    let mid = start + (end - start) / 2;
+
+    let idx = nodes.len();
+    nodes.push(Node::Branch { bounds, right: 0 });
+
+    build(primitives, start, mid, nodes);
+
+    let right = nodes.len();
+    build(primitives, mid, end, nodes);
+
+    nodes[idx] = Node::Branch { bounds, right };
+}
+
+/// Ray trace `leaves` through `view` into a `width` × `height` image.
+pub fn trace(leaves: &[Leaf], view: &View, width: usize, height: usize, settings: &TraceSettings) -> Image {
+    trace_surface(&Bvh::new(leaves), view, width, height, settings)
+}
+
+/// Ray trace any `surface` through `view` into a `width` × `height` image.
+pub fn trace_surface<S: Surface + Sync>(surface: &S, view: &View, width: usize, height: usize, settings: &TraceSettings) -> Image {
+    use rayon::prelude::*;
+
+    let viewport = view.viewport(width, height);
+
+    let offset = SURFACE_OFFSET * surface.size();
+    let ao_distance = settings.ao_distance * surface.size();
+    let samples = settings.samples.max(1);
+
+    let mut image = Image::new(width, height);
+
+    image.pixels.par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            row.iter_mut().enumerate().for_each(|(x, pixel)| {
+                let mut rng = pixel_rng(x, y);
+
+                let color = (0..samples)
+                    .map(|_| {
+                        let jitter = if samples > 1 { (rng.gen::<f32>(), rng.gen::<f32>()) } else { (0.5, 0.5) };
+                        let ray = viewport.ray(&Point2::new(x as f32 + jitter.0, y as f32 + jitter.1));
+
+                        match surface.cast(&ray, INFINITY) {
+                            Some(hit) => shade(surface, &ray, &hit, settings, offset, ao_distance, &mut rng),
+                            None => settings.background,
+                        }
+                    })
+                    .fold(Vector3::zeros(), |acc, c| acc + c) / samples as f32;
+
+                *pixel = to_display(&color);
+            });
+        });
+
+    image
+}
+
+/// Linear color of `hit`.
+fn shade<S: Surface, R: Rng>(surface: &S, ray: &Ray3<f32>, hit: &Hit, settings: &TraceSettings, offset: f32, ao_distance: f32, rng: &mut R) -> Vector3<f32> {
+    // light the side of the face the ray arrived from
+    let normal = if hit.normal.dot(&ray.dir) > 0.0 { -hit.normal } else { hit.normal };
+    let point = ray.origin + ray.dir * hit.toi + normal * offset;
+
+    let lambert = normal.dot(&settings.light).max(0.0);
+    let direct = if lambert > 0.0 && !surface.occluded(&Ray3::new(point, settings.light), INFINITY) {
+        lambert
+    } else {
+        0.0
+    };
+
+    let ambient = if settings.ao_samples == 0 {
+        settings.ambient
+    } else {
+        let open = (0..settings.ao_samples)
+            .filter(|_| !surface.occluded(&Ray3::new(point, cosine_sample(&normal, rng)), ao_distance))
+            .count();
+
+        settings.ambient * open as f32 / settings.ao_samples as f32
+    };
+
+    hit.color * (ambient + (1.0 - settings.ambient) * direct)
+}
+
+/// Random direction in the hemisphere around `normal`, distributed by the cosine of its angle to
+/// it.
+fn cosine_sample<R: Rng>(normal: &Vector3<f32>, rng: &mut R) -> Vector3<f32> {
+    let (u, v) = (rng.gen::<f32>(), rng.gen::<f32>());
+    let (r, theta) = (u.sqrt(), 2.0 * std::f32::consts::PI * v);
+
+    // any basis around the normal will do
+    let helper = if normal[0].abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let tangent = normal.cross(&helper).normalize();
+    let bitangent = normal.cross(&tangent);
+
+    (tangent * (r * theta.cos()) + bitangent * (r * theta.sin()) + normal * (1.0 - u).max(0.0).sqrt()).normalize()
+}
+
+fn pixel_rng(x: usize, y: usize) -> XorShiftRng {
+    let mut seed = *b"3frac ray tracer";
+
+    (0..4).for_each(|i| {
+        seed[i] ^= (x >> (8 * i)) as u8;
+        seed[i + 4] ^= (y >> (8 * i)) as u8;
+    });
+
+    XorShiftRng::from_seed(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ifs::Leaf, projection::View, render::to_display};
+    use na::{Matrix4, Vector3};
+    use rayon::ThreadPoolBuilder;
+    use super::{trace, TraceSettings};
+
+    #[test]
+    fn trace_is_independent_of_thread_count() {
+        let leaves = [
+            (Vector3::new(-0.4, 0.0, 0.0), Vector3::new(1.0, 0.2, 0.2)),
+            (Vector3::new(0.4, 0.3, 0.2), Vector3::new(0.2, 0.2, 1.0)),
+        ]
+            .iter()
+            .enumerate()
+            .map(|(i, &(offset, color))| Leaf {
+                address: vec![i],
+                transform: Matrix4::new_translation(&offset) * Matrix4::new_scaling(0.5),
+                color,
+            })
+            .collect::<Vec<_>>();
+
+        let view = View::default();
+        let settings = TraceSettings::default();
+
+        let single = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let serial = single.install(|| trace(&leaves, &view, 32, 24, &settings));
+        let parallel = trace(&leaves, &view, 32, 24, &settings);
+
+        // make sure the cubes are actually in view, so there's something to compare
+        let background = to_display(&settings.background);
+        assert!(serial.pixels.iter().any(|pixel| *pixel != background));
+
+        assert_eq!(serial, parallel);
+    }
+}
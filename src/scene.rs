@@ -0,0 +1,90 @@
+//! On-disk scene format.
+
+use crate::component::ComponentSpec;
+use failure::Fallible;
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+/// Version written into every saved scene. Bump this whenever the format changes shape.
+pub const SCENE_VERSION: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub version: u32,
+    pub components: Vec<ComponentSpec>,
+}
+
+impl Scene {
+    pub fn new(components: Vec<ComponentSpec>) -> Self {
+        Scene {
+            version: SCENE_VERSION,
+            components,
+        }
+    }
+
+    pub fn from_str(s: &str) -> Fallible<Self> {
+        let scene: Scene = ron::de::from_str(s)?;
+
+        if scene.version > SCENE_VERSION {
+            bail!("scene version {} is newer than the latest supported version ({})", scene.version, SCENE_VERSION);
+        }
+
+        Ok(scene)
+    }
+
+    pub fn to_string(&self) -> Fallible<String> {
+        Ok(ron::ser::to_string_pretty(self, Default::default())?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Fallible<Self> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+
+        Scene::from_str(&contents)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Fallible<()> {
+        let contents = self.to_string()?;
+        File::create(path)?.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::ComponentSpec;
+    use na::{UnitQuaternion, Vector3};
+    use super::{Scene, SCENE_VERSION};
+
+    fn scene() -> Scene {
+        let mut first = ComponentSpec::default();
+        first.origin = Vector3::new(0.25, -0.5, 1.0);
+        first.orientation = UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3);
+        first.scale = Vector3::new(0.5, 0.25, 0.75);
+        first.color = Vector3::new(1.0, 0.5, 0.0);
+
+        Scene::new(vec![first, ComponentSpec::default()])
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let text = scene().to_string().unwrap();
+        let loaded = Scene::from_str(&text).unwrap();
+
+        assert_eq!(loaded.version, SCENE_VERSION);
+        assert_eq!(loaded.components.len(), 2);
+        assert_eq!(loaded.to_string().unwrap(), text);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut scene = scene();
+        scene.version = SCENE_VERSION + 1;
+
+        assert!(Scene::from_str(&scene.to_string().unwrap()).is_err());
+    }
+}
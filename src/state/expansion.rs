@@ -0,0 +1,184 @@
+use crate::{
+    ifs::{
+        self,
+        Leaf,
+        Map,
+        PARTITION_LEAVES,
+        System,
+    },
+    projection::Viewport,
+    CHAOS_BATCH_SIZE,
+};
+use na::{Point3, Vector3};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+};
+
+/// Runs fractal expansion on a worker thread so the window keeps rendering the previous result
+/// while a new one is computed.
+#[derive(Debug, Default)]
+pub(super) struct Expansion {
+    /// Maps and request of the most recently requested expansion.
+    key: Option<(Vec<Map>, Request)>,
+
+    job: Option<Job>,
+}
+
+/// What to expand a system into.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Request {
+    /// Every leaf at the given depth.
+    Full { depth: usize },
+
+    /// Largest cells first, down to at most `max_depth`, until `budget` leaves are used.
+    Budgeted { max_depth: usize, budget: usize },
+
+    /// Subdivide every cell larger than `pixels` on screen, largest first, down to at most
+    /// `max_depth` and until `budget` leaves are used.
+    Adaptive { viewport: Viewport, pixels: f32, max_depth: usize, budget: usize },
+
+    /// `points` points on the attractor from the chaos game.
+    Chaos { points: usize },
+}
+
+/// The result of an expansion.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Expanded {
+    pub leaves: Vec<Leaf>,
+    pub points: Vec<(Point3<f32>, Vector3<f32>)>,
+}
+
+#[derive(Debug)]
+enum Batch {
+    Leaves(Vec<Leaf>),
+    Points(Vec<(Point3<f32>, Vector3<f32>)>),
+}
+
+#[derive(Debug)]
+struct Job {
+    cancelled: Arc<AtomicBool>,
+    receiver: Receiver<Batch>,
+
+    /// Everything received so far.
+    expanded: Expanded,
+}
+
+impl Expansion {
+    /// Whether the latest requested expansion (finished or not) was for these maps and request.
+    pub fn is_current(&self, maps: &[Map], request: &Request) -> bool {
+        self.key.as_ref().map_or(false, |(key_maps, key_request)| key_request == request && key_maps.as_slice() == maps)
+    }
+
+    /// Begin expanding `system`, abandoning any expansion still in flight.
+    pub fn start(&mut self, system: System, request: Request) {
+        self.cancel();
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        self.key = Some((system.maps().to_vec(), request.clone()));
+
+        let worker_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            use rayon::prelude::*;
+
+            let depth = match request {
+                Request::Full { depth } => depth,
+
+                Request::Budgeted { max_depth, budget } => {
+                    if let Some(leaves) = system.refine(max_depth, budget, 0.0, ifs::volume, &worker_cancelled) {
+                        let _ = sender.send(Batch::Leaves(leaves));
+                    }
+                    return;
+                },
+
+                Request::Adaptive { viewport, pixels, max_depth, budget } => {
+                    let leaves = system.refine(max_depth, budget, pixels, |tsfm| viewport.projected_size(tsfm), &worker_cancelled);
+
+                    if let Some(leaves) = leaves {
+                        let _ = sender.send(Batch::Leaves(leaves));
+                    }
+                    return;
+                },
+
+                Request::Chaos { points } => {
+                    let mut game = system.chaos_game();
+                    let mut remaining = points;
+
+                    while remaining > 0 && !worker_cancelled.load(Ordering::Relaxed) {
+                        let batch = game.by_ref().take(remaining.min(CHAOS_BATCH_SIZE)).collect::<Vec<_>>();
+
+                        if batch.is_empty() || sender.send(Batch::Points(batch)).is_err() {
+                            return;
+                        }
+
+                        remaining = remaining.saturating_sub(CHAOS_BATCH_SIZE);
+                    }
+
+                    return;
+                },
+            };
+
+            let partitions = system.partitions(depth, PARTITION_LEAVES);
+
+            // expand a round of subtrees in parallel, then hand them back in address order
+            for round in partitions.chunks(rayon::current_num_threads()) {
+                if worker_cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let batches = round.par_iter()
+                    .map(|prefix| system.subtree(prefix, depth).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+
+                // a failed send means nobody's listening anymore
+                if batches.into_iter().any(|batch| sender.send(Batch::Leaves(batch)).is_err()) {
+                    return;
+                }
+            }
+        });
+
+        self.job = Some(Job {
+            cancelled,
+            receiver,
+            expanded: Expanded::default(),
+        });
+    }
+
+    /// Stop the running expansion, if any, and forget what was last requested.
+    pub fn cancel(&mut self) {
+        if let Some(job) = self.job.take() {
+            job.cancelled.store(true, Ordering::Relaxed);
+        }
+
+        self.key = None;
+    }
+
+    /// Collect whatever the worker has produced. Returns the complete result once the expansion
+    /// has finished, and `None` while it's still running or if nothing is running.
+    pub fn poll(&mut self) -> Option<Expanded> {
+        let finished = {
+            let job = self.job.as_mut()?;
+
+            loop {
+                match job.receiver.try_recv() {
+                    Ok(Batch::Leaves(leaves)) => job.expanded.leaves.extend(leaves),
+                    Ok(Batch::Points(points)) => job.expanded.points.extend(points),
+                    Err(TryRecvError::Empty) => break false,
+                    Err(TryRecvError::Disconnected) => break true,
+                }
+            }
+        };
+
+        if finished {
+            self.job.take().map(|job| job.expanded)
+        } else {
+            None
+        }
+    }
+}
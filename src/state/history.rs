@@ -0,0 +1,168 @@
+//! Undo and redo for edits to the scene.
+//!
+//! Every edit begins with a checkpoint naming what kind of edit it is. The checkpoint saves the
+//! components as they were, unless it continues the previous edit: scrolling the same adjustment
+//! on the same components, or tapping the same key, merges into one undo step as long as it keeps
+//! happening. Drags take a single checkpoint when they first move, so they're always one step.
+//!
+//! Checkpoints are only taken right before something actually changes, so edits that turn out to
+//! be no-ops don't throw away the redo history.
+
+use crate::component::ComponentSpec;
+use std::time::{Duration, Instant};
+
+/// What an edit does, for deciding whether consecutive edits form one undo step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    /// One step of a continuous adjustment (e.g. scrolling the scale), named by what it adjusts.
+    /// Runs of the same adjustment to the same component merge.
+    Adjust(&'static str),
+
+    /// A self-contained edit, never merged with anything.
+    Discrete,
+}
+
+#[derive(Clone, Debug)]
+pub struct History {
+    /// Scene states to go back to, most recent last.
+    undo: Vec<Vec<ComponentSpec>>,
+
+    /// Scene states undone, most recently undone last.
+    redo: Vec<Vec<ComponentSpec>>,
+
+    /// The last adjustment recorded, the uids it applied to and when, so it can be continued.
+    last: Option<(&'static str, Vec<usize>, Instant)>,
+
+    limit: usize,
+    coalesce: Duration,
+}
+
+impl History {
+    /// A history of at most `limit` steps, merging adjustments less than `coalesce` apart.
+    pub fn new(limit: usize, coalesce: Duration) -> Self {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last: None,
+            limit,
+            coalesce,
+        }
+    }
+
+    /// Note that an `edit` to the components with uids `targets` is about to happen. `snapshot` is
+    /// only called if this starts a new undo step.
+    pub fn checkpoint<F: FnOnce() -> Vec<ComponentSpec>>(&mut self, edit: Edit, targets: Vec<usize>, snapshot: F) {
+        let now = Instant::now();
+
+        let continues = match (edit, &self.last) {
+            (Edit::Adjust(name), Some((last_name, last_targets, at))) =>
+                name == *last_name && targets == *last_targets && now.duration_since(*at) < self.coalesce,
+            _ => false,
+        };
+
+        self.last = match edit {
+            Edit::Adjust(name) => Some((name, targets, now)),
+            Edit::Discrete => None,
+        };
+
+        if continues {
+            return;
+        }
+
+        self.undo.push(snapshot());
+        self.redo.clear();
+
+        if self.undo.len() > self.limit {
+            let excess = self.undo.len() - self.limit;
+            self.undo.drain(..excess);
+        }
+    }
+
+    /// Step back from `current`, returning the state to restore.
+    pub fn undo(&mut self, current: Vec<ComponentSpec>) -> Option<Vec<ComponentSpec>> {
+        let previous = self.undo.pop()?;
+
+        self.redo.push(current);
+        self.last = None;
+
+        Some(previous)
+    }
+
+    /// Step forward from `current` again, returning the state to restore.
+    pub fn redo(&mut self, current: Vec<ComponentSpec>) -> Option<Vec<ComponentSpec>> {
+        let next = self.redo.pop()?;
+
+        self.undo.push(current);
+        self.last = None;
+
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::ComponentSpec;
+    use std::time::Duration;
+    use super::{Edit, History};
+
+    /// A distinct scene state for each `n`, told apart by length.
+    fn scene(n: usize) -> Vec<ComponentSpec> {
+        (0..n).map(|_| ComponentSpec::default()).collect()
+    }
+
+    #[test]
+    fn undo_and_redo_step_through_checkpoints() {
+        let mut history = History::new(16, Duration::from_secs(60));
+
+        history.checkpoint(Edit::Discrete, vec![], || scene(0));
+        history.checkpoint(Edit::Discrete, vec![], || scene(1));
+
+        assert_eq!(history.undo(scene(2)).map(|s| s.len()), Some(1));
+        assert_eq!(history.undo(scene(1)).map(|s| s.len()), Some(0));
+        assert_eq!(history.undo(scene(0)), None);
+
+        assert_eq!(history.redo(scene(0)).map(|s| s.len()), Some(1));
+        assert_eq!(history.redo(scene(1)).map(|s| s.len()), Some(2));
+        assert_eq!(history.redo(scene(2)), None);
+
+        // a fresh edit after undoing discards what was undone
+        history.undo(scene(2));
+        history.checkpoint(Edit::Discrete, vec![], || scene(1));
+        assert_eq!(history.redo(scene(3)), None);
+    }
+
+    #[test]
+    fn adjustments_coalesce_within_the_window() {
+        let mut history = History::new(16, Duration::from_secs(60));
+
+        history.checkpoint(Edit::Adjust("scale"), vec![0], || scene(0));
+        history.checkpoint(Edit::Adjust("scale"), vec![0], || panic!("continued adjustment took a snapshot"));
+
+        assert_eq!(history.undo(scene(1)).map(|s| s.len()), Some(0));
+        assert_eq!(history.undo(scene(0)), None);
+    }
+
+    #[test]
+    fn adjustments_split_outside_the_window() {
+        let mut history = History::new(16, Duration::from_secs(0));
+
+        history.checkpoint(Edit::Adjust("scale"), vec![0], || scene(0));
+        history.checkpoint(Edit::Adjust("scale"), vec![0], || scene(1));
+
+        assert_eq!(history.undo(scene(2)).map(|s| s.len()), Some(1));
+        assert_eq!(history.undo(scene(1)).map(|s| s.len()), Some(0));
+    }
+
+    #[test]
+    fn different_adjustments_split() {
+        let mut history = History::new(16, Duration::from_secs(60));
+
+        history.checkpoint(Edit::Adjust("scale"), vec![0], || scene(0));
+        history.checkpoint(Edit::Adjust("color"), vec![0], || scene(1));
+        history.checkpoint(Edit::Adjust("color"), vec![1], || scene(2));
+
+        assert_eq!(history.undo(scene(3)).map(|s| s.len()), Some(2));
+        assert_eq!(history.undo(scene(2)).map(|s| s.len()), Some(1));
+        assert_eq!(history.undo(scene(1)).map(|s| s.len()), Some(0));
+    }
+}
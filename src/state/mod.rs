@@ -1,28 +1,79 @@
 use crate::{
+    ADAPTIVE_MAX_DEPTH,
+    ADAPTIVE_PIXELS,
+    ADAPTIVE_SETTLE_FRAMES,
     BOX_EDGES,
-    component::Component,
+    CHAOS_POINTS,
+    component::ComponentSpec,
+    CUBE_BUDGET,
+    export::{
+        self,
+        ply::PlyFormat,
+        stl::StlReport,
+    },
+    ifs,
+    PLY_MAX_POINTS,
+    projection::Viewport,
+    render::{
+        histogram::{Histogram, ToneMapping},
+        trace::{self, TraceSettings},
+    },
+    scene::Scene,
     SELECTION_BBOX_SCALE,
+    STL_VOXEL_RESOLUTION,
+    UNDO_COALESCE_MS,
+    UNDO_LIMIT,
 };
+use failure::Fallible;
 use glfw;
-use kiss3d::{
-    camera::ArcBall,
-    scene::SceneNode,
-};
+use kiss3d::camera::ArcBall;
 use nc::query::Ray3;
+use self::expansion::{Expansion, Request};
+use self::history::History;
+pub use self::history::Edit;
 use self::render_state::RenderState;
 use self::world::{DragState, WorldState};
 use std::{
     cell::RefCell,
+    fs::File,
+    io::BufWriter,
+    path::Path,
     rc::{Rc, Weak},
+    sync::atomic::AtomicBool,
+    time::Duration,
 };
 
+mod expansion;
+mod history;
 mod render_state;
 pub mod world;
 
 pub struct State {
     iteration_depth: usize,
+    cube_budget: usize,
+
+    /// Whether to pick depth per branch from its size on screen rather than using
+    /// `iteration_depth`.
+    adaptive: bool,
+
+    /// The viewport as of the last frame, and how many frames in a row it's been unchanged, so
+    /// adaptive mode can wait for the camera to settle.
+    last_viewport: Option<Viewport>,
+    still_frames: usize,
+
+    /// Whether to draw the attractor as a chaos game point cloud instead of cubes.
+    chaos: bool,
+
+    coloring: ifs::Coloring,
+
+    /// Components most recently copied, for pasting when the system clipboard doesn't hold a
+    /// scene.
+    copied: Vec<ComponentSpec>,
+
     world: WorldState,
     render_state: RenderState,
+    expansion: Expansion,
+    history: History,
 }
 
 impl State {
@@ -35,22 +86,82 @@ impl State {
 
         State {
             iteration_depth: 0,
-            world: WorldState::new(&mut render_state.window),
+            cube_budget: CUBE_BUDGET,
+            adaptive: false,
+            last_viewport: None,
+            still_frames: 0,
+            chaos: false,
+            coloring: ifs::Coloring::default(),
+            copied: Vec::new(),
+            world: WorldState::new(),
             render_state,
+            expansion: Expansion::default(),
+            history: History::new(UNDO_LIMIT, Duration::from_millis(UNDO_COALESCE_MS)),
         }
     }
 
     pub fn render(&mut self) {
+        let hovered = self.nearest_intersection()
+            .and_then(|(comp, _)| comp.upgrade())
+            .map(|comp| comp.borrow().uid());
+
+        self.render_state.sync_components(&self.world.components, hovered);
+        self.render_state.draw_points();
+
+        if self.iteration_depth == 0 && !self.adaptive && !self.chaos {
+            self.expansion.cancel();
+            self.render_state.show_components();
+
+            return;
+        }
+
+        let viewport = self.render_state.viewport();
+
+        if self.last_viewport == Some(viewport) {
+            self.still_frames += 1;
+        } else {
+            self.last_viewport = Some(viewport);
+            self.still_frames = 0;
+        }
+
+        let system = self.system();
+
+        // when everything fits, skip the priority queue and enumerate the whole tree in parallel
+        let request = if self.chaos {
+            Request::Chaos { points: CHAOS_POINTS }
+        } else if self.adaptive {
+            Request::Adaptive {
+                viewport,
+                pixels: ADAPTIVE_PIXELS,
+                max_depth: ADAPTIVE_MAX_DEPTH,
+                budget: self.cube_budget,
+            }
+        } else if system.leaf_count(self.iteration_depth).map_or(false, |count| count <= self.cube_budget) {
+            Request::Full { depth: self.iteration_depth }
+        } else {
+            Request::Budgeted { max_depth: self.iteration_depth, budget: self.cube_budget }
+        };
+
+        // adaptive expansions depend on the camera, so rather than restarting on every frame of an
+        // orbit or zoom, keep showing the last one until the camera settles
+        let settling = self.adaptive && !self.chaos && self.still_frames < ADAPTIVE_SETTLE_FRAMES;
+
+        if !settling && (self.render_state.dirty || !self.expansion.is_current(system.maps(), &request)) {
+            self.render_state.dirty = false;
+            self.expansion.start(system, request);
+        }
 
+        if let Some(expanded) = self.expansion.poll() {
+            self.render_state.show_iterated(&expanded.leaves, &expanded.points);
+        }
     }
 
     pub fn project_mouse(&self) -> Ray3<f32> {
         self.render_state.project_mouse()
     }
 
-    pub fn nearest_intersection(&self) -> Option<(Weak<RefCell<Component>>, Point3<f32>)> {
+    pub fn nearest_intersection(&self) -> Option<(Weak<RefCell<ComponentSpec>>, Point3<f32>)> {
         use std::cmp::Ordering;
-        use nc::query::RayCast;
 
         let components = self.world.components;
         let mouse_projection = self.render_state.project_mouse();
@@ -60,9 +171,7 @@ impl State {
             .filter_map(|(idx, comp)| {
                 let comp = comp.borrow();
 
-                comp.cuboid()
-                    .toi_with_ray(&comp.isometric_part(), &mouse_projection, true)
-                    .map(|x| (idx, x))
+                comp.toi_with_ray(&mouse_projection).map(|x| (idx, x))
             })
             .min_by(|x, y| {
                 x.1.partial_cmp(&y.1).unwrap_or(Ordering::Less)
@@ -83,20 +192,247 @@ impl State {
         self.iteration_depth -= 1
     }
 
+    pub fn toggle_chaos(&mut self) {
+        self.chaos = !self.chaos
+    }
+
+    pub fn is_chaos(&self) -> bool {
+        self.chaos
+    }
+
+    pub fn toggle_adaptive(&mut self) {
+        self.adaptive = !self.adaptive
+    }
+
+    /// Switch leaves between hue blending and coloring by their measure.
+    pub fn toggle_coloring(&mut self) {
+        self.coloring = match self.coloring {
+            ifs::Coloring::Hue => ifs::Coloring::Measure,
+            ifs::Coloring::Measure => ifs::Coloring::Hue,
+        };
+
+        self.render_state.dirty = true;
+    }
+
+    /// Shift probability toward or away from the selected component by `delta`.
+    ///
+    /// Components left on automatic weights are first pinned to their current normalized
+    /// probability so the adjustment is relative to what's on screen.
+    pub fn adjust_weight(&mut self, delta: f32) {
+        let selection = match self.selection() {
+            Some(comp) => comp,
+            None => return,
+        };
+
+        self.begin_edit(Edit::Adjust("weight"));
+
+        let selected = selection.borrow().uid();
+        let probabilities = self.system().probabilities();
+
+        self.world.components.iter()
+            .zip(probabilities)
+            .for_each(|(comp, p)| {
+                let mut comp = comp.borrow_mut();
+                let weight = comp.weight.unwrap_or(p);
+
+                comp.weight = Some(if comp.uid() == selected {
+                    (weight + delta).max(0.0)
+                } else {
+                    weight
+                });
+            });
+    }
+
+    /// Return every component to weights proportional to volume.
+    pub fn reset_weights(&mut self) {
+        if self.world.components.iter().all(|comp| comp.borrow().weight.is_none()) {
+            return;
+        }
+
+        self.begin_edit(Edit::Discrete);
+        self.world.components.iter().for_each(|comp| comp.borrow_mut().weight = None);
+    }
+
+    pub fn increase_budget(&mut self) {
+        self.cube_budget *= 2;
+    }
+
+    pub fn decrease_budget(&mut self) {
+        self.cube_budget = (self.cube_budget / 2).max(1);
+    }
+
     pub fn is_dragging(&self) -> bool {
         self.world.drag_state.is_some()
     }
 
-    pub fn selection(&self) -> Option<Rc<RefCell<Component>>> {
-        self.world.selection.as_ref().map(|sel| sel.clone())
+    /// The primary selection: the most recently selected box.
+    pub fn selection(&self) -> Option<Rc<RefCell<ComponentSpec>>> {
+        self.world.selection.last().cloned()
+    }
+
+    /// Every selected box.
+    pub fn selected(&self) -> Vec<Rc<RefCell<ComponentSpec>>> {
+        self.world.selection.clone()
+    }
+
+    pub fn is_selected(&self, component: &ComponentSpec) -> bool {
+        self.world.selection.iter().any(|sel| *sel.borrow() == *component)
     }
 
-    pub fn select(&mut self, component: Rc<RefCell<Component>>) {
-        self.world.selection = Some(component)
+    /// Select only `component`.
+    pub fn select(&mut self, component: Rc<RefCell<ComponentSpec>>) {
+        self.world.selection = vec![component];
+    }
+
+    /// Add `component` to the selection (making it primary), or remove it if it's already there.
+    pub fn toggle_selected(&mut self, component: Rc<RefCell<ComponentSpec>>) {
+        let existing = self.world.selection.iter().position(|sel| *sel.borrow() == *component.borrow());
+
+        match existing {
+            Some(idx) => {
+                self.world.selection.remove(idx);
+            },
+            None => self.world.selection.push(component),
+        }
+    }
+
+    /// Move `component`, which must already be selected, to the end of the selection, so
+    /// single-box edits and drags follow it.
+    pub fn make_primary(&mut self, component: Rc<RefCell<ComponentSpec>>) {
+        self.world.selection.retain(|sel| *sel.borrow() != *component.borrow());
+        self.world.selection.push(component);
     }
 
     pub fn deselect(&mut self) {
-        self.world.selection = None;
+        self.world.selection.clear();
+    }
+
+    /// Mean of the origins of the selected boxes, which group edits pivot around.
+    pub fn selection_centroid(&self) -> Option<Vector3<f32>> {
+        if self.world.selection.is_empty() {
+            return None;
+        }
+
+        let sum = self.world.selection.iter().fold(Vector3::zeros(), |acc, comp| acc + comp.borrow().origin);
+
+        Some(sum / self.world.selection.len() as f32)
+    }
+
+    /// Move every selected box by `offset`.
+    pub fn translate_selected(&mut self, offset: &Vector3<f32>) {
+        self.world.selection.iter().for_each(|comp| comp.borrow_mut().origin += offset);
+    }
+
+    /// Rotate the selection by `rotation`. A single box turns about its own axes, as before; a
+    /// group turns rigidly about its centroid, about world axes.
+    pub fn rotate_selected(&mut self, rotation: &UnitQuaternion<f32>) {
+        if self.world.selection.len() == 1 {
+            self.world.selection[0].borrow_mut().orientation *= *rotation;
+            return;
+        }
+
+        let centroid = match self.selection_centroid() {
+            Some(centroid) => centroid,
+            None => return,
+        };
+
+        self.world.selection.iter().for_each(|comp| {
+            let mut comp = comp.borrow_mut();
+
+            comp.origin = centroid + rotation.transform_vector(&(comp.origin - centroid));
+            comp.orientation = rotation * comp.orientation;
+        });
+    }
+
+    /// Scale the selection along local `axis` (or every axis, for `None`) by `amount`. A single box
+    /// has `amount` added to its scale, as before, so scrolling one axis can pass through zero into a
+    /// reflection. A group is scaled by a factor of `e^amount` about its centroid, moving the
+    /// boxes apart or together along with their sizes; unlike `1 + amount`, that stays positive
+    /// for any negative amount, so a group never flips or collapses to a point.
+    pub fn scale_selected(&mut self, axis: Option<usize>, amount: f32) {
+        if self.world.selection.len() == 1 {
+            let mut comp = self.world.selection[0].borrow_mut();
+
+            match axis {
+                // uniform scaling grows or shrinks each axis without flipping it
+                None => comp.scale.apply(|x| x.signum() * 0.0f32.max(x.abs() + amount)),
+                Some(axis) => comp.scale[axis] += amount,
+            }
+
+            return;
+        }
+
+        let centroid = match self.selection_centroid() {
+            Some(centroid) => centroid,
+            None => return,
+        };
+
+        // scrolling back undoes this exactly
+        let factor = amount.exp();
+
+        self.world.selection.iter().for_each(|comp| {
+            let mut comp = comp.borrow_mut();
+
+            match axis {
+                None => {
+                    comp.scale *= factor;
+                    comp.origin = centroid + (comp.origin - centroid) * factor;
+                },
+                Some(axis) => {
+                    comp.scale[axis] *= factor;
+
+                    // spread along the same direction in the world as the boxes' own axis
+                    let mut local = Vector3::zeros();
+                    local[axis] = 1.0;
+
+                    let direction = comp.orientation.transform_vector(&local);
+                    let offset = comp.origin - centroid;
+                    comp.origin += direction * (offset.dot(&direction) * (factor - 1.0));
+                },
+            }
+        });
+    }
+
+    /// Start dragging out a selection rectangle from window coordinates `start`.
+    pub fn begin_box_select(&mut self, start: Point2<f32>) {
+        self.world.box_select = Some(start);
+    }
+
+    pub fn is_box_selecting(&self) -> bool {
+        self.world.box_select.is_some()
+    }
+
+    /// Finish a selection rectangle at window coordinates `end`, selecting every box whose center
+    /// lies inside it. With `extend`, those boxes are added to the current selection instead of
+    /// replacing it.
+    pub fn end_box_select(&mut self, end: Point2<f32>, extend: bool) {
+        let start = match self.world.box_select.take() {
+            Some(start) => start,
+            None => return,
+        };
+
+        let (min_x, max_x) = (start[0].min(end[0]), start[0].max(end[0]));
+        let (min_y, max_y) = (start[1].min(end[1]), start[1].max(end[1]));
+
+        let viewport = self.render_state.viewport();
+
+        let inside = self.world.components.iter()
+            .filter(|comp| {
+                viewport.project(&Point3::from_coordinates(comp.borrow().origin))
+                    .map_or(false, |p| p[0] >= min_x && p[0] <= max_x && p[1] >= min_y && p[1] <= max_y)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !extend {
+            self.deselect();
+        }
+
+        for comp in inside {
+            if !self.is_selected(&comp.borrow()) {
+                self.world.selection.push(comp);
+            }
+        }
     }
 
     pub fn drag(&mut self, drag_state: DragState) {
@@ -115,12 +451,124 @@ impl State {
         self.render_state.window.glfw_window()
     }
 
-    pub fn new_component(&mut self, comp: Component) {
+    pub fn clipboard(&mut self) -> String {
+        self.render_state.window.glfw_window().get_clipboard_string()
+    }
+
+    pub fn set_clipboard(&mut self, contents: &str) {
+        self.render_state.window.glfw_window_mut().set_clipboard_string(contents)
+    }
+
+    /// Record an undo checkpoint before changing the scene. Adjustments apply to the selection, so
+    /// repeats only merge while it stays the same.
+    pub fn begin_edit(&mut self, edit: Edit) {
+        let targets = self.world.selection.iter().map(|comp| comp.borrow().uid()).collect();
+        let components = &self.world.components;
+
+        self.history.checkpoint(edit, targets, || components.iter().map(|comp| comp.borrow().clone()).collect());
+    }
+
+    pub fn undo(&mut self) {
+        let current = self.snapshot();
+
+        if let Some(previous) = self.history.undo(current) {
+            self.restore(previous);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let current = self.snapshot();
+
+        if let Some(next) = self.history.redo(current) {
+            self.restore(next);
+        }
+    }
+
+    fn snapshot(&self) -> Vec<ComponentSpec> {
+        self.world.components.iter().map(|comp| comp.borrow().clone()).collect()
+    }
+
+    /// Replace every component, keeping whatever's still there selected. Components keep their
+    /// uids through a snapshot, so the renderer only rebuilds those that changed.
+    fn restore(&mut self, components: Vec<ComponentSpec>) {
+        let selected = self.world.selection.iter().map(|comp| comp.borrow().uid()).collect::<Vec<_>>();
+
+        self.stop_dragging();
+
+        self.world.components = components.into_iter()
+            .map(|comp| Rc::new(RefCell::new(comp)))
+            .collect();
+
+        self.world.selection = selected.into_iter()
+            .filter_map(|uid| self.world.components.iter().find(|comp| comp.borrow().uid() == uid).cloned())
+            .collect();
+    }
+
+    pub fn new_component(&mut self, comp: ComponentSpec) {
         self.world.components.push(Rc::new(RefCell::new(comp)));
     }
 
-    pub fn root_group(&mut self) -> &mut SceneNode {
-        &mut self.world.root_group
+    /// Add copies of the selected boxes, moved by `offset`, and select the copies instead.
+    pub fn duplicate_selected(&mut self, offset: &Vector3<f32>) {
+        let copies = self.world.selection.iter()
+            .map(|comp| {
+                let mut copy = comp.borrow().duplicate();
+                copy.origin += offset;
+
+                copy
+            })
+            .collect::<Vec<_>>();
+
+        self.add_and_select(copies);
+    }
+
+    /// Copy the selected boxes, both within the app and to the system clipboard as a scene file,
+    /// so they can be pasted into another running instance or saved by hand.
+    pub fn copy_selected(&mut self) -> Fallible<()> {
+        if self.world.selection.is_empty() {
+            return Ok(());
+        }
+
+        self.copied = self.world.selection.iter().map(|comp| comp.borrow().clone()).collect();
+
+        let text = Scene::new(self.copied.clone()).to_string()?;
+        self.set_clipboard(&text);
+
+        Ok(())
+    }
+
+    /// Paste the boxes on the system clipboard if it holds a scene, otherwise the ones last copied
+    /// here. Pasted boxes keep their exact transforms and colors, and become the selection.
+    pub fn paste(&mut self) -> Fallible<()> {
+        let text = self.clipboard();
+
+        // deserializing always assigns fresh uids, so the same scene can be pasted any number of
+        // times
+        let components = match Scene::from_str(&text) {
+            Ok(scene) => scene.components,
+            Err(_) if !self.copied.is_empty() => self.copied.iter().map(ComponentSpec::duplicate).collect(),
+            Err(e) => return Err(e),
+        };
+
+        self.add_and_select(components);
+
+        Ok(())
+    }
+
+    fn add_and_select(&mut self, components: Vec<ComponentSpec>) {
+        if components.is_empty() {
+            return;
+        }
+
+        self.begin_edit(Edit::Discrete);
+        self.deselect();
+
+        components.into_iter().for_each(|comp| {
+            let comp = Rc::new(RefCell::new(comp));
+
+            self.world.components.push(comp.clone());
+            self.world.selection.push(comp);
+        });
     }
 
     pub fn toggle_wireframes(&mut self) -> {
@@ -128,30 +576,178 @@ impl State {
     }
 
     pub fn delete_selected(&mut self) {
-        self.selection().map(|comp| {
-            let mut components = self.world.components;
+        if self.world.selection.is_empty() {
+            return;
+        }
 
-            let idx = components.iter().position(|c| *c.borrow() == *comp.borrow()).expect("selection didn't exist in vec");
-            let comp = components.swap_remove(idx);
+        self.begin_edit(Edit::Discrete);
 
-            {
-                comp.borrow_mut().scene_node.unlink();
-            }
-        });
+        let selection = &self.world.selection;
+        self.world.components.retain(|comp| !selection.iter().any(|sel| *sel.borrow() == *comp.borrow()));
+
+        self.deselect();
+    }
+
+    pub fn save_scene<P: AsRef<Path>>(&self, path: P) -> Fallible<()> {
+        let components = self.world.components.iter()
+            .map(|comp| comp.borrow().clone())
+            .collect();
+
+        Scene::new(components).save(path)
+    }
+
+    pub fn load_scene<P: AsRef<Path>>(&mut self, path: P) -> Fallible<()> {
+        let scene = Scene::load(path)?;
+
+        self.begin_edit(Edit::Discrete);
+        self.deselect();
+        self.stop_dragging();
+
+        self.world.components.clear();
+        scene.components.into_iter().for_each(|comp| self.new_component(comp));
+
+        self.render_state.dirty = true;
+
+        Ok(())
+    }
+
+    /// The iterated function system described by the current components.
+    pub fn system(&self) -> ifs::System {
+        ifs::System::new(self.world.components.iter().map(|comp| comp.borrow().map()).collect())
+            .with_coloring(self.coloring)
+    }
+
+    /// Write every leaf at the current iteration depth to an OBJ file.
+    pub fn export_obj<P: AsRef<Path>>(&self, path: P) -> Fallible<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        let system = self.system();
+
+        export::obj::write_obj(writer, system.leaves(self.iteration_depth))?;
+
+        Ok(())
+    }
+
+    /// Write every leaf at the current iteration depth to a glTF file, with the binary buffer
+    /// alongside it under the same name with a `.bin` extension.
+    pub fn export_gltf<P: AsRef<Path>>(&self, path: P) -> Fallible<()> {
+        let path = path.as_ref();
+        let bin_path = path.with_extension("bin");
+        let bin_uri = bin_path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format_err!("invalid glTF export path {}", path.display()))?;
+
+        let writer = BufWriter::new(File::create(path)?);
+        let bin_writer = BufWriter::new(File::create(&bin_path)?);
+
+        export::gltf::write_gltf(writer, bin_writer, bin_uri, self.system().leaves(self.iteration_depth))?;
+
+        Ok(())
+    }
+
+    /// Write the center of every leaf at `depth` to a PLY point cloud. `depth` may be beyond
+    /// anything drawable, since leaves are streamed to disk rather than collected, but not past
+    /// `PLY_MAX_POINTS` leaves.
+    pub fn export_points<P: AsRef<Path>>(&self, path: P, depth: usize, format: PlyFormat) -> Fallible<()> {
+        let system = self.system();
+        let count = match system.leaf_count(depth) {
+            Some(count) if count <= PLY_MAX_POINTS => count,
+            _ => bail!("depth {} has more than {} leaves to export", depth, PLY_MAX_POINTS),
+        };
+
+        let writer = BufWriter::new(File::create(path)?);
+        let points = system.leaves(depth).map(|leaf| (leaf.center(), leaf.color));
+
+        export::ply::write_ply(writer, format, count, points)?;
+
+        Ok(())
+    }
+
+    /// Write `count` chaos game points to a PLY point cloud, streaming them straight to disk.
+    pub fn export_chaos_points<P: AsRef<Path>>(&self, path: P, count: usize, format: PlyFormat) -> Fallible<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        let system = self.system();
+
+        export::ply::write_ply(writer, format, count, system.chaos_game())?;
+
+        Ok(())
+    }
+
+    /// Render `points` chaos game points through the current camera as a density histogram, at
+    /// `scale` times the window's size, and write it to a PNG.
+    pub fn render_histogram<P: AsRef<Path>>(&self, path: P, points: usize, scale: f32) -> Fallible<()> {
+        let viewport = self.render_state.viewport();
+        let viewport = Viewport::new(viewport.transform, (viewport.width * scale).round(), (viewport.height * scale).round());
+
+        Histogram::render(&self.system(), &viewport, points)
+            .to_image(&ToneMapping::default())
+            .save_png(path)
+    }
+
+    /// Ray trace the leaves at the current iteration depth through the current camera, at `scale`
+    /// times the window's size, and write it to a PNG.
+    ///
+    /// Like the viewer, this only expands the whole tree if it fits in the cube budget, and
+    /// otherwise traces the budgeted refinement, so deep trees never have to be held in full.
+    pub fn render_trace<P: AsRef<Path>>(&self, path: P, scale: f32) -> Fallible<()> {
+        let viewport = self.render_state.viewport();
+        let (width, height) = ((viewport.width * scale).round() as usize, (viewport.height * scale).round() as usize);
+
+        let system = self.system();
+        let depth = self.iteration_depth;
+
+        let leaves = match system.leaf_count(depth) {
+            Some(count) if count <= self.cube_budget => system.par_leaves(depth),
+            _ => system.refine(depth, self.cube_budget, 0.0, ifs::volume, &AtomicBool::new(false))
+                .expect("refinement is never cancelled here"),
+        };
+
+        trace::trace(&leaves, &self.render_state.view(), width, height, &TraceSettings::default())
+            .save_png(path)
+    }
+
+    pub fn iteration_depth(&self) -> usize {
+        self.iteration_depth
+    }
+
+    /// Write the union of every leaf at the current iteration depth to a binary STL file.
+    pub fn export_stl<P: AsRef<Path>>(&self, path: P) -> Fallible<StlReport> {
+        let writer = BufWriter::new(File::create(path)?);
+        let leaves = self.system().leaves(self.iteration_depth).collect::<Vec<_>>();
+
+        Ok(export::stl::write_stl(writer, &leaves, STL_VOXEL_RESOLUTION)?)
     }
 
     pub fn drag_update(&mut self) {
-        self.world.drag_state.iter().for_each(|drag_state| {
+        let mouse_projection = self.project_mouse();
+
+        // the grabbed box follows the mouse, and the rest of the selection moves with it
+        let offset = self.world.drag_state.as_ref().and_then(|drag_state| {
             self.selection().map(|comp| {
-                let mut comp = comp.borrow_mut();
+                let comp = comp.borrow();
 
                 let rotation = comp.orientation / drag_state.origin_orientation;
                 let new_terminus = mouse_projection.origin + drag_state.camera_dist * mouse_projection.dir.normalize();
                 let new_origin = new_terminus - rotation.transform_vector(&drag_state.local_handle_offset);
 
-                comp.origin = new_origin.coords;
-            });
+                new_origin.coords - comp.origin
+            })
         });
+
+        let offset = match offset {
+            Some(offset) if offset != Vector3::zeros() => offset,
+            _ => return,
+        };
+
+        let first_move = self.world.drag_state.as_ref().map_or(false, |drag_state| !drag_state.moved);
+        if first_move {
+            self.begin_edit(Edit::Discrete);
+
+            if let Some(ref mut drag_state) = self.world.drag_state {
+                drag_state.moved = true;
+            }
+        }
+
+        self.translate_selected(&offset);
     }
 
     pub fn draw_wireframes(&mut self) {
@@ -177,7 +773,7 @@ impl State {
             });
 
             if iteration_depth > 0 {
-                if selection.upgrade().map_or(false, |x| *x.borrow() == *comp) {
+                if self.is_selected(&comp) {
                     return;
                 }
 
@@ -189,36 +785,94 @@ impl State {
             }
         });
 
-        self.selection().iter().for_each(|comp| {
-            let comp = comp.borrow();
+        let selection_color = Point3::new(1.0, 0.5, 0.5);
+
+        match self.world.selection.len() {
+            0 => {},
+
+            1 => {
+                let comp = self.world.selection[0].borrow();
+
+                let transform = if iteration_depth > 0 {
+                    comp.transform().to_homogeneous()
+                } else {
+                    comp.transform().to_homogeneous() * Matrix4::new_scaling(SELECTION_BBOX_SCALE)
+                };
+
+                BOX_EDGES.iter()
+                    .for_each(|(p1, p2)|
+                        window.draw_line(&transform.transform_point(&p1), &transform.transform_point(&p2), &selection_color)
+                    );
+            },
+
+            // a group gets one axis-aligned box around every corner of every selected box
+            _ => {
+                use std::f32::{INFINITY, NEG_INFINITY};
+
+                let (min, max) = self.world.selection.iter()
+                    .flat_map(|comp| {
+                        let transform = comp.borrow().transform();
+                        BOX_EDGES.iter().map(move |(p, _)| transform.transform_point(p)).collect::<Vec<_>>()
+                    })
+                    .fold((Vector3::repeat(INFINITY), Vector3::repeat(NEG_INFINITY)), |(min, max), p| {
+                        (min.zip_map(&p.coords, f32::min), max.zip_map(&p.coords, f32::max))
+                    });
+
+                let center = (min + max) / 2.0;
+                let half = (max - min) / 2.0 * SELECTION_BBOX_SCALE;
+
+                let transform = Matrix4::new_translation(&center) * Matrix4::new_nonuniform_scaling(&(half * 2.0));
+
+                BOX_EDGES.iter()
+                    .for_each(|(p1, p2)|
+                        window.draw_line(&transform.transform_point(&p1), &transform.transform_point(&p2), &selection_color)
+                    );
+            },
+        }
 
-            let scale = if iteration_depth > 0 {
-                Matrix4::new_nonuniform_scaling(&comp.scale)
-            } else {
-                Matrix4::new_scaling(SELECTION_BBOX_SCALE) * Matrix4::new_nonuniform_scaling(&comp.scale)
-            };
+        // kiss3d's planar coordinates are centered on the window, with y up
+        if let Some(start) = self.world.box_select {
+            let (x, y) = window.glfw_window().get_cursor_pos();
+            let (half_width, half_height) = (window.width() / 2.0, window.height() / 2.0);
 
-            let transform = comp.isometric_part().to_homogeneous() * scale;
+            let to_planar = |x: f32, y: f32| Point2::new(x - half_width, half_height - y);
+            let corners = [
+                to_planar(start[0], start[1]),
+                to_planar(x as f32, start[1]),
+                to_planar(x as f32, y as f32),
+                to_planar(start[0], y as f32),
+            ];
 
-            BOX_EDGES.iter()
-                .for_each(|(p1, p2)|
-                    window.draw_line(&transform.transform_point(&p1), &transform.transform_point(&p2), &Point3::new(1.0, 0.5, 0.5))
-                );
-        });
+            (0..4).for_each(|i| window.draw_planar_line(&corners[i], &corners[(i + 1) % 4], &Point3::new(1.0, 0.5, 0.5)));
+        }
     }
 
     pub fn draw_overlay_text(&mut self) {
         let mut window = self.render_state.window;
 
         let pos = Point2::new(window.width() * 2.0 - 300.0, window.height() * 2.0 - 165.0);
-        window.draw_text(&format!("iterations: {}", iteration_depth), &pos, &self.render_state.font, &Point3::new(0.9, 0.9, 0.9));
+        let iterations = if self.chaos {
+            "chaos game".to_owned()
+        } else if self.adaptive {
+            "adaptive".to_owned()
+        } else {
+            self.iteration_depth.to_string()
+        };
+        window.draw_text(&format!("iterations: {}", iterations), &pos, &self.render_state.font, &Point3::new(0.9, 0.9, 0.9));
+
+        let cube_count = self.render_state.leaf_count;
+        window.draw_text(&format!("cubes: {} / {}", cube_count, self.cube_budget), &Point2::new(pos[0], pos[1] + 75.0), &self.render_state.font, &Point3::new(0.9, 0.9, 0.9));
 
-        let cube_count = components.len().pow(iteration_depth as u32 + 1);
-        window.draw_text(&format!("cubes: {}", cube_count), &Point2::new(pos[0], pos[1] + 75.0), &self.render_state.font, &Point3::new(0.9, 0.9, 0.9));
+        let probabilities = self.system().probabilities();
 
         self.selection().iter().for_each(|comp| {
             let comp = comp.borrow();
 
+            let probability = self.world.components.iter()
+                .position(|c| *c.borrow() == *comp)
+                .map(|idx| probabilities[idx])
+                .unwrap_or(0.0);
+
             let matrix = comp.transform().matrix().clone();
             let mut matrix_fmt = String::new();
             for i in 0..4 {
@@ -228,7 +882,26 @@ impl State {
                 matrix_fmt.push('\n');
             }
 
-            let text = format!("selected transform (matrix representation)\n{}", matrix_fmt);
+            let (roll, pitch, yaw) = comp.orientation.to_euler_angles();
+            let decomposition = format!(
+                "translation {: >6.2} {: >6.2} {: >6.2}\nrotation    {: >6.1} {: >6.1} {: >6.1}\nscale       {: >6.2} {: >6.2} {: >6.2}\nshear       {: >6.2} {: >6.2} {: >6.2}",
+                comp.origin[0], comp.origin[1], comp.origin[2],
+                roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees(),
+                comp.scale[0], comp.scale[1], comp.scale[2],
+                comp.shear[0], comp.shear[1], comp.shear[2],
+            );
+
+            let weight = match comp.weight {
+                Some(_) => format!("probability {: >6.3}", probability),
+                None => format!("probability {: >6.3} (auto)", probability),
+            };
+
+            let mut text = format!("selected transform (matrix representation)\n{}\n{}\n{}", matrix_fmt, decomposition, weight);
+
+            if self.world.selection.len() > 1 {
+                text.push_str(&format!("\n({} boxes selected)", self.world.selection.len()));
+            }
+
             window.draw_text(&text, &Point2::new(10.0, 10.0), &roboto_font, &Point3::new(0.9, 0.9, 0.9));
         }
     }
@@ -1,17 +1,32 @@
 use crate::{
+    component::ComponentSpec,
+    ifs::Leaf,
+    mesh::{self, MeshChunk},
+    projection::{View, Viewport},
     NAME,
     ROBOTO_TTF,
     VERSION,
 };
 use glfw::MouseButton::*;
 use kiss3d::{
-    camera::ArcBall,
+    camera::{ArcBall, Camera},
     light::Light,
+    resource::Mesh,
+    scene::SceneNode,
     text::Font,
     window::Window,
 };
+use na::{
+    Matrix4,
+    Point2,
+    Point3,
+    Vector2,
+    Vector3,
+};
+use nc::query::Ray3;
 use std::{
     cell::RefCell,
+    collections::HashMap,
     default::Default,
     rc::{Rc, Weak},
 };
@@ -24,6 +39,19 @@ pub(super) struct RenderState {
     pub font: Rc<Font>,
     pub point_set: Vec<(Point3<f32>, Point3<f32>)>,
     pub dirty: bool,
+
+    /// Parent of the scene nodes representing components directly.
+    pub root_group: SceneNode,
+
+    /// Parent of the scene nodes produced by fractal iteration.
+    pub iterated_group: SceneNode,
+
+    /// Number of leaves currently drawn.
+    pub leaf_count: usize,
+
+    /// Scene node for each component, keyed by component uid, along with the transform its
+    /// geometry was built for.
+    component_nodes: HashMap<usize, (SceneNode, Matrix4<f32>)>,
 }
 
 impl RenderState {
@@ -37,6 +65,116 @@ impl RenderState {
 
         Ray3::new(loc, dir)
     }
+
+    /// The current camera, independent of the window.
+    pub fn view(&self) -> View {
+        View {
+            eye: self.camera.eye(),
+            target: self.camera.at(),
+            fovy: self.camera.fovy(),
+        }
+    }
+
+    /// The current camera projection onto the window.
+    pub fn viewport(&self) -> Viewport {
+        Viewport::new(self.camera.transformation(), self.window.width(), self.window.height())
+    }
+
+    pub fn draw_points(&mut self) {
+        let window = &mut self.window;
+        self.point_set.iter().for_each(|(pt, color)| window.draw_point(pt, color));
+    }
+
+    /// Show the components themselves, hiding any iterated geometry.
+    pub fn show_components(&mut self) {
+        if self.root_group.is_visible() {
+            return;
+        }
+
+        self.leaf_count = 0;
+        self.iterated_group.unlink();
+        self.iterated_group = self.window.add_group();
+        self.point_set.clear();
+
+        self.root_group.set_visible(true);
+    }
+
+    /// Replace the iterated geometry with `leaves`, batched into as few meshes as possible, and
+    /// `points`.
+    pub fn show_iterated(&mut self, leaves: &[Leaf], points: &[(Point3<f32>, Vector3<f32>)]) {
+        self.root_group.set_visible(false);
+        self.leaf_count = leaves.len();
+
+        self.point_set = points.iter()
+            .map(|(pt, color)| (*pt, Point3::from_coordinates(*color)))
+            .collect();
+
+        self.iterated_group.unlink();
+        self.iterated_group = self.window.add_group();
+        self.iterated_group.enable_backface_culling(true);
+
+        for chunk in mesh::build_chunks(leaves) {
+            add_chunk(&mut self.iterated_group, chunk);
+        }
+    }
+
+    /// Bring the component scene nodes in line with `components`, creating and unlinking nodes
+    /// as components come and go.
+    pub fn sync_components(&mut self, components: &[Rc<RefCell<ComponentSpec>>], hovered: Option<usize>) {
+        use palette::{Blend, LinSrgb};
+
+        let live = components.iter()
+            .map(|comp| comp.borrow().uid())
+            .collect::<Vec<_>>();
+
+        self.component_nodes.retain(|uid, (node, _)| {
+            let keep = live.contains(uid);
+
+            if !keep {
+                node.unlink();
+            }
+
+            keep
+        });
+
+        for comp in components.iter() {
+            let comp = comp.borrow();
+
+            let transform = comp.transform().to_homogeneous();
+
+            // kiss3d nodes can't shear, so each component's box is baked into its own mesh, rebuilt
+            // whenever the component changes shape
+            let stale = self.component_nodes.get(&comp.uid()).map_or(true, |(_, built)| *built != transform);
+            if stale {
+                let node = add_chunk(&mut self.root_group, MeshChunk::cube(&transform, comp.color));
+
+                if let Some((mut old, _)) = self.component_nodes.insert(comp.uid(), (node, transform)) {
+                    old.unlink();
+                }
+            }
+
+            let node = &mut self.component_nodes.get_mut(&comp.uid()).unwrap().0;
+
+            if hovered == Some(comp.uid()) {
+                let selected_color = LinSrgb::new(1.0, 0.7, 0.7);
+                let cur_color = LinSrgb::new(comp.color[0], comp.color[1], comp.color[2]);
+                let result = selected_color.multiply(cur_color);
+
+                node.set_color(result.red, result.green, result.blue);
+            } else {
+                node.set_color(comp.color[0], comp.color[1], comp.color[2]);
+            }
+        }
+    }
+}
+
+fn add_chunk(parent: &mut SceneNode, chunk: MeshChunk) -> SceneNode {
+    let mesh = Mesh::new(chunk.coords, chunk.faces, Some(chunk.normals), None, false);
+
+    let mut node = parent.add_mesh(Rc::new(RefCell::new(mesh)), Vector3::repeat(1.0));
+    node.set_color(chunk.color[0], chunk.color[1], chunk.color[2]);
+
+    node
 }
 
 impl Default for RenderState {
@@ -57,7 +195,8 @@ impl Default for RenderState {
         window.set_framerate_limit(Some(70));
         window.set_background_color(0.1, 0.1, 0.1);
 
-
+        let root_group = window.add_group();
+        let iterated_group = window.add_group();
 
         RenderState {
             wireframes_enabled: true,
@@ -66,6 +205,10 @@ impl Default for RenderState {
             font: roboto_font,
             point_set: Vec::new(),
             dirty: false,
+            root_group,
+            iterated_group,
+            leaf_count: 0,
+            component_nodes: HashMap::new(),
         }
     }
 }
\ No newline at end of file
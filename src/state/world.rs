@@ -1,8 +1,5 @@
-use crate::component::Component;
-use kiss3d::{
-    scene::SceneNode,
-    window::Window,
-};
+use crate::component::ComponentSpec;
+use na::{Point2, UnitQuaternion, Vector3};
 use std::{
     cell::RefCell,
     default::Default,
@@ -12,17 +9,18 @@ use std::{
 #[derive(Clone, Debug)]
 pub(super) struct WorldState {
     /// The set of boxes in the world.
-    pub components: Vec<Rc<RefCell<Component>>>,
+    pub components: Vec<Rc<RefCell<ComponentSpec>>>,
 
     /// Description of currently-dragged box. Exists iff a box is being dragged.
     pub drag_state: Option<DragState>,
 
-    /// A reference to the currently-selected box.
-    pub selection: Option<Rc<RefCell<Component>>>,
-
-    pub root_group: SceneNode,
+    /// The currently-selected boxes, in the order they were selected. The last one is the primary
+    /// selection, which single-box edits and the overlay apply to.
+    pub selection: Vec<Rc<RefCell<ComponentSpec>>>,
 
-    pub iterated_group: SceneNode,
+    /// Window coordinates where an in-progress box selection started. Exists iff the user is
+    /// dragging out a selection rectangle.
+    pub box_select: Option<Point2<f32>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -36,16 +34,19 @@ pub struct DragState {
 
     /// The camera's distance from its intersection with the box.
     pub camera_dist: f32,
+
+    /// Whether the drag has moved anything yet. The undo checkpoint is taken on the first move, so
+    /// clicking a box without dragging it leaves the history alone.
+    pub moved: bool,
 }
 
 impl WorldState
-    pub(super) fn new(window: &mut Window) -> Self {
+    pub(super) fn new() -> Self {
         WorldState {
             components: Vec::new(),
             drag_state: None,
-            selection: None,
-            root_group: window.add_group(),
-            iterated_group: window.add_group(),
+            selection: Vec::new(),
+            box_select: None,
         }
     }
 }